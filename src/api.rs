@@ -1,179 +1,690 @@
+use crate::client::XrpcClient;
 use crate::error::{AppError, Result as AppResult};
-use reqwest::Client;
+use crate::identity::normalize_actor;
+use crate::output::Render;
+use crate::utils::rate_limit;
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
-use crate::auth::get_session;
-use log::{info, error};
+use std::collections::HashSet;
+use std::fmt;
+use std::time::Duration;
 
-pub const BLUESKY_API_URL: &str = "https://bsky.social/xrpc";
+/// Collection NSID holding follow records in a repo
+pub const FOLLOW_COLLECTION: &str = "app.bsky.graph.follow";
+
+/// Collection NSID holding block records in a repo
+pub const BLOCK_COLLECTION: &str = "app.bsky.graph.block";
 
 #[derive(Debug, Serialize, Deserialize)]
-struct Profile {
-    handle: String,
-    did: String,
-    display_name: Option<String>,
-    description: Option<String>,
-    followers_count: i32,
-    follows_count: i32,
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+    pub handle: String,
+    pub did: String,
+    pub display_name: Option<String>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub followers_count: i32,
+    #[serde(default)]
+    pub follows_count: i32,
+    #[serde(default, skip_serializing)]
+    viewer: Option<ViewerState>,
+}
+
+impl Render for Profile {
+    fn text(&self) -> String {
+        let mut lines = vec![
+            format!("Profile for @{}", self.handle),
+            format!("DID: {}", self.did),
+        ];
+        if let Some(name) = &self.display_name {
+            lines.push(format!("Display Name: {}", name));
+        }
+        if let Some(desc) = &self.description {
+            lines.push(format!("Description: {}", desc));
+        }
+        lines.push(format!("Followers: {}", self.followers_count));
+        lines.push(format!("Following: {}", self.follows_count));
+        lines.join("\n")
+    }
+}
+
+/// Relationship between the logged-in user and a profile
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ViewerState {
+    /// AT-URI of our follow record for this account, if we follow it
+    pub following: Option<String>,
+    /// AT-URI of our block record for this account, if we block it
+    pub blocking: Option<String>,
+    #[serde(default)]
+    pub blocked_by: bool,
+}
+
+/// AT-URI and CID of a record stored in a repo
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordRef {
+    pub uri: String,
+    pub cid: String,
+}
+
+/// Outcome of the `follow` command
+#[derive(Debug, Serialize)]
+pub struct FollowResult {
+    pub actor: String,
+    pub did: String,
+    pub record: RecordRef,
+}
+
+impl Render for FollowResult {
+    fn text(&self) -> String {
+        format!("Followed {} ({})", self.actor, self.record.uri)
+    }
+}
+
+/// Outcome of the `unfollow` command; `record` is `None` if we didn't follow
+#[derive(Debug, Serialize)]
+pub struct UnfollowResult {
+    pub actor: String,
+    pub did: String,
+    pub record: Option<RecordRef>,
+}
+
+impl Render for UnfollowResult {
+    fn text(&self) -> String {
+        match &self.record {
+            Some(record) => format!("Unfollowed {} ({})", self.actor, record.uri),
+            None => format!("You don't follow {}", self.actor),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
+struct ListedRecord {
+    uri: String,
+    cid: String,
+    value: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListRecordsPage {
+    records: Vec<ListedRecord>,
+    cursor: Option<String>,
+}
+
+/// An account entry from a followers or follows listing
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Follower {
     pub did: String,
     pub handle: String,
-    pub indexed_at: chrono::DateTime<chrono::Utc>,
+    pub indexed_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    pub viewer: Option<ViewerState>,
 }
 
-pub async fn print_my_profile() -> AppResult<()> {
-    let session = get_session()?;
+/// Largest page size accepted by the graph endpoints
+pub const MAX_PAGE_SIZE: u32 = 100;
 
-    let client = Client::new();
-    let response = client
-        .get(format!("{}/com.atproto.repo.getProfile", BLUESKY_API_URL))
-        .header("Authorization", format!("Bearer {}", session.access_jwt))
-        .query(&[("actor", session.handle)])
-        .send()
-        .await?;
+/// Controls how a [`GraphPager`] walks a paginated listing
+#[derive(Debug, Clone, Copy)]
+pub struct PageOptions {
+    /// Items requested per page, clamped to `1..=MAX_PAGE_SIZE`
+    pub page_size: u32,
+    /// Stop after this many items in total
+    pub max_items: Option<usize>,
+}
 
-    let profile: Profile = response.json().await?;
-    
-    println!("Profile for @{}", profile.handle);
-    println!("DID: {}", profile.did);
-    if let Some(name) = profile.display_name {
-        println!("Display Name: {}", name);
+impl Default for PageOptions {
+    fn default() -> Self {
+        Self {
+            page_size: MAX_PAGE_SIZE,
+            max_items: None,
+        }
     }
-    if let Some(desc) = profile.description {
-        println!("Description: {}", desc);
+}
+
+/// Which social graph listing a [`GraphPager`] walks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphKind {
+    Followers,
+    Follows,
+}
+
+impl GraphKind {
+    pub const ALL: [GraphKind; 2] = [GraphKind::Followers, GraphKind::Follows];
+
+    /// Name of the list, as stored with its snapshots
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GraphKind::Followers => "followers",
+            GraphKind::Follows => "follows",
+        }
     }
-    println!("Followers: {}", profile.followers_count);
-    println!("Following: {}", profile.follows_count);
 
-    Ok(())
+    fn nsid(self) -> &'static str {
+        match self {
+            GraphKind::Followers => "app.bsky.graph.getFollowers",
+            GraphKind::Follows => "app.bsky.graph.getFollows",
+        }
+    }
 }
 
-pub async fn lookup_profile(handle: &str) -> AppResult<()> {
-    let session = get_session()?;
+impl std::str::FromStr for GraphKind {
+    type Err = String;
 
-    let client = Client::new();
-    let response = client
-        .get(format!("{}/com.atproto.repo.getProfile", BLUESKY_API_URL))
-        .header("Authorization", format!("Bearer {}", session.access_jwt))
-        .query(&[("actor", handle)])
-        .send()
-        .await?;
+    /// Accepts `followers` and `follows` (or `following`), ignoring case
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "followers" => Ok(GraphKind::Followers),
+            "follows" | "following" => Ok(GraphKind::Follows),
+            _ => Err(format!("expected followers or follows, got {}", s)),
+        }
+    }
+}
 
-    let profile: Profile = response.json().await?;
-    
-    println!("Profile for @{}", profile.handle);
-    println!("DID: {}", profile.did);
-    if let Some(name) = profile.display_name {
-        println!("Display Name: {}", name);
-    }
-    if let Some(desc) = profile.description {
-        println!("Description: {}", desc);
-    }
-    println!("Followers: {}", profile.followers_count);
-    println!("Following: {}", profile.follows_count);
-
-    Ok(())
-}
-
-pub async fn mirror_follows(handle: &str) -> AppResult<()> {
-    let _session = get_session()?;
-
-    println!("Fetching follows for @{}...", handle);
-    
-    // TODO: Implement actual follow mirroring
-    // This will require:
-    // 1. Fetching the user's follows
-    // 2. Checking which ones we don't follow
-    // 3. Following them one by one with rate limiting
-    
-    println!("Mirror follows functionality coming soon!");
-    Ok(())
-}
-
-pub async fn get_followers() -> AppResult<Vec<Follower>> {
-    let session = get_session()?;
-    let client = Client::new();
-    
-    let response = client
-        .get(format!("{}/app.bsky.graph.getFollowers", BLUESKY_API_URL))
-        .header("Authorization", format!("Bearer {}", session.access_jwt))
-        .send()
-        .await?;
+impl fmt::Display for GraphKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphPage {
+    #[serde(alias = "followers", alias = "follows")]
+    items: Vec<Follower>,
+    cursor: Option<String>,
+}
+
+/// Follows the `cursor` of getFollowers/getFollows one page at a time
+pub struct GraphPager<'a> {
+    client: &'a XrpcClient,
+    kind: GraphKind,
+    actor: String,
+    options: PageOptions,
+    cursor: Option<String>,
+    fetched: usize,
+    done: bool,
+}
+
+impl<'a> GraphPager<'a> {
+    pub fn new(client: &'a XrpcClient, kind: GraphKind, actor: &str, options: PageOptions) -> Self {
+        Self {
+            client,
+            kind,
+            actor: actor.to_string(),
+            options,
+            cursor: None,
+            fetched: 0,
+            done: false,
+        }
+    }
+
+    /// Fetches the next page, or `None` once the cursor or the item cap runs out
+    pub async fn next_page(&mut self) -> AppResult<Option<Vec<Follower>>> {
+        let remaining = self.options.max_items.map(|max| max.saturating_sub(self.fetched));
+        if self.done || remaining == Some(0) {
+            return Ok(None);
+        }
+
+        let mut limit = self.options.page_size.clamp(1, MAX_PAGE_SIZE);
+        if let Some(remaining) = remaining {
+            limit = limit.min(remaining.try_into().unwrap_or(MAX_PAGE_SIZE));
+        }
+
+        let mut params = vec![
+            ("actor", self.actor.clone()),
+            ("limit", limit.to_string()),
+        ];
+        if let Some(cursor) = &self.cursor {
+            params.push(("cursor", cursor.clone()));
+        }
+
+        let mut page: GraphPage = self.client.query(self.kind.nsid(), &params).await?;
+        if let Some(remaining) = remaining {
+            page.items.truncate(remaining);
+        }
+        self.fetched += page.items.len();
+
+        // Stop on a missing or repeated cursor so a misbehaving server can't loop us forever.
+        // Short or empty pages don't end the listing: the AppView filters out
+        // taken-down and blocked actors after paging.
+        match page.cursor {
+            Some(cursor) if self.cursor.as_ref() != Some(&cursor) => {
+                self.cursor = Some(cursor);
+            }
+            _ => self.done = true,
+        }
+
+        Ok(Some(page.items))
+    }
+
+    /// Drains every remaining page into a single list
+    pub async fn collect(mut self) -> AppResult<Vec<Follower>> {
+        let mut all = Vec::new();
+        while let Some(page) = self.next_page().await? {
+            all.extend(page);
+        }
+        Ok(all)
+    }
+}
+
+/// Fetches the logged-in user's profile
+///
+/// Looked up by DID: the stored handle is stale after a rename until the next
+/// token refresh.
+pub async fn my_profile(client: &XrpcClient) -> AppResult<Profile> {
+    let did = client.session()?.did;
+    lookup_profile(client, &did).await
+}
+
+/// Fetches the profile of a handle or DID
+pub async fn lookup_profile(client: &XrpcClient, actor: &str) -> AppResult<Profile> {
+    let actor = normalize_actor(actor)?;
+    client
+        .query("app.bsky.actor.getProfile", &[("actor", actor)])
+        .await
+}
+
+/// Options for [`mirror_follows`]
+#[derive(Debug, Clone, Copy)]
+pub struct MirrorOptions {
+    /// Only report what would be followed
+    pub dry_run: bool,
+    /// Follow at most this many accounts
+    pub max: Option<usize>,
+    /// Follows issued per minute
+    pub per_minute: u32,
+}
+
+impl Default for MirrorOptions {
+    fn default() -> Self {
+        Self {
+            dry_run: false,
+            max: None,
+            per_minute: 30,
+        }
+    }
+}
+
+/// What happened to one account during [`mirror_follows`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MirrorStatus {
+    WouldFollow,
+    Followed,
+    Failed,
+}
 
-    if !response.status().is_success() {
-        let error_text = response.text().await?;
-        error!("Failed to get followers: {}", error_text);
-        return Err(AppError::Api(error_text));
+#[derive(Debug, Serialize)]
+pub struct MirrorEntry {
+    pub did: String,
+    pub handle: String,
+    pub status: MirrorStatus,
+}
+
+/// Outcome of [`mirror_follows`]
+#[derive(Debug, Serialize)]
+pub struct MirrorReport {
+    pub target: String,
+    pub dry_run: bool,
+    pub skipped: usize,
+    pub followed: usize,
+    pub failed: usize,
+    pub accounts: Vec<MirrorEntry>,
+}
+
+impl Render for MirrorReport {
+    fn text(&self) -> String {
+        let mut lines = vec![format!(
+            "{} accounts to follow, {} skipped",
+            self.accounts.len(),
+            self.skipped
+        )];
+        for entry in &self.accounts {
+            lines.push(match entry.status {
+                MirrorStatus::WouldFollow => format!("Would follow @{}", entry.handle),
+                MirrorStatus::Followed => format!("Followed @{}", entry.handle),
+                MirrorStatus::Failed => format!("Failed to follow @{}", entry.handle),
+            });
+        }
+        if self.dry_run {
+            lines.push(format!("Dry run: {} accounts would be followed", self.accounts.len()));
+        } else {
+            lines.push(format!("Followed {} accounts ({} failed)", self.followed, self.failed));
+        }
+        lines.join("\n")
     }
 
-    let followers: Vec<Follower> = response.json().await?;
-    Ok(followers)
+    fn records(&self) -> AppResult<Vec<serde_json::Value>> {
+        self.accounts
+            .iter()
+            .map(|entry| Ok(serde_json::to_value(entry)?))
+            .collect()
+    }
 }
 
-pub async fn get_following() -> AppResult<Vec<Follower>> {
-    let session = get_session()?;
-    let client = Client::new();
-    
-    let response = client
-        .get(format!("{}/app.bsky.graph.getFollows", BLUESKY_API_URL))
-        .header("Authorization", format!("Bearer {}", session.access_jwt))
-        .send()
+/// Largest batch accepted by `app.bsky.actor.getProfiles`
+const PROFILES_BATCH_SIZE: usize = 25;
+
+#[derive(Debug, Deserialize)]
+struct ProfilesResponse {
+    profiles: Vec<Follower>,
+}
+
+/// Follows every account `handle` follows that we don't already follow
+///
+/// Ourselves, accounts we block or that block us, and accounts the AppView no
+/// longer returns profiles for (deactivated or taken down) are skipped.
+///
+/// `on_progress` is called with the position, total and outcome of each
+/// follow attempt as it happens.
+pub async fn mirror_follows(
+    client: &XrpcClient,
+    handle: &str,
+    options: MirrorOptions,
+    mut on_progress: impl FnMut(usize, usize, &MirrorEntry),
+) -> AppResult<MirrorReport> {
+    let me = client.session()?.did;
+
+    info!("Fetching follows for @{}", handle);
+    let target_follows = GraphPager::new(client, GraphKind::Follows, handle, PageOptions::default())
+        .collect()
         .await?;
 
-    if !response.status().is_success() {
-        let error_text = response.text().await?;
-        error!("Failed to get following: {}", error_text);
-        return Err(AppError::Api(error_text));
+    info!("Fetching accounts you already follow");
+    let already_following: HashSet<String> = get_following(client, PageOptions::default())
+        .await?
+        .into_iter()
+        .map(|account| account.did)
+        .collect();
+
+    let mut seen = HashSet::new();
+    let mut skipped = 0;
+    let mut candidates = Vec::new();
+    for account in target_follows {
+        let viewer = account.viewer.clone().unwrap_or_default();
+        let is_excluded = account.did == me
+            || already_following.contains(&account.did)
+            || viewer.following.is_some()
+            || viewer.blocking.is_some()
+            || viewer.blocked_by
+            || !seen.insert(account.did.clone());
+
+        if is_excluded {
+            skipped += 1;
+        } else {
+            candidates.push(account);
+        }
     }
 
-    let following: Vec<Follower> = response.json().await?;
-    Ok(following)
+    let active = active_dids(client, &candidates).await?;
+    let before = candidates.len();
+    candidates.retain(|account| active.contains(&account.did));
+    skipped += before - candidates.len();
+
+    if let Some(max) = options.max {
+        candidates.truncate(max);
+    }
+
+    let delay = Duration::from_secs_f64(60.0 / f64::from(options.per_minute.max(1)));
+    let total = candidates.len();
+    let mut report = MirrorReport {
+        target: handle.to_string(),
+        dry_run: options.dry_run,
+        skipped,
+        followed: 0,
+        failed: 0,
+        accounts: Vec::with_capacity(total),
+    };
+
+    for (i, account) in candidates.into_iter().enumerate() {
+        let status = if options.dry_run {
+            MirrorStatus::WouldFollow
+        } else {
+            match follow(client, &account.did).await {
+                Ok(_) => {
+                    report.followed += 1;
+                    MirrorStatus::Followed
+                }
+                Err(e) => {
+                    report.failed += 1;
+                    warn!("Failed to follow @{}: {}", account.handle, e);
+                    MirrorStatus::Failed
+                }
+            }
+        };
+
+        let entry = MirrorEntry {
+            did: account.did,
+            handle: account.handle,
+            status,
+        };
+        if !options.dry_run {
+            on_progress(i + 1, total, &entry);
+        }
+        report.accounts.push(entry);
+
+        if !options.dry_run && i + 1 < total {
+            rate_limit(delay).await;
+        }
+    }
+
+    Ok(report)
 }
 
-pub async fn follow(did: &str) -> AppResult<()> {
-    let session = get_session()?;
-    let client = Client::new();
-    
-    let response = client
-        .post(format!("{}/app.bsky.graph.follow", BLUESKY_API_URL))
-        .header("Authorization", format!("Bearer {}", session.access_jwt))
-        .json(&serde_json::json!({
-            "subject": did,
-            "createdAt": chrono::Utc::now().to_rfc3339()
-        }))
-        .send()
+/// Returns the DIDs among `accounts` that still resolve to an active profile
+async fn active_dids(client: &XrpcClient, accounts: &[Follower]) -> AppResult<HashSet<String>> {
+    let mut active = HashSet::new();
+    for batch in accounts.chunks(PROFILES_BATCH_SIZE) {
+        let params: Vec<_> = batch
+            .iter()
+            .map(|account| ("actors", account.did.as_str()))
+            .collect();
+        let response: ProfilesResponse = client.query("app.bsky.actor.getProfiles", &params).await?;
+        active.extend(response.profiles.into_iter().map(|profile| profile.did));
+    }
+    Ok(active)
+}
+
+/// Fetches every account following the logged-in user
+pub async fn get_followers(client: &XrpcClient, options: PageOptions) -> AppResult<Vec<Follower>> {
+    let did = client.session()?.did;
+    GraphPager::new(client, GraphKind::Followers, &did, options)
+        .collect()
+        .await
+}
+
+/// Fetches every account the logged-in user follows
+pub async fn get_following(client: &XrpcClient, options: PageOptions) -> AppResult<Vec<Follower>> {
+    let did = client.session()?.did;
+    GraphPager::new(client, GraphKind::Follows, &did, options)
+        .collect()
+        .await
+}
+
+/// Follows an account by creating an `app.bsky.graph.follow` record
+pub async fn follow(client: &XrpcClient, did: &str) -> AppResult<RecordRef> {
+    let repo = client.session()?.did;
+    client
+        .procedure(
+            "com.atproto.repo.createRecord",
+            &serde_json::json!({
+                "repo": repo,
+                "collection": FOLLOW_COLLECTION,
+                "record": {
+                    "$type": FOLLOW_COLLECTION,
+                    "subject": did,
+                    "createdAt": chrono::Utc::now().to_rfc3339()
+                }
+            }),
+        )
+        .await
+}
+
+/// Blocks an account by creating an `app.bsky.graph.block` record
+pub async fn block(client: &XrpcClient, did: &str) -> AppResult<RecordRef> {
+    let repo = client.session()?.did;
+    client
+        .procedure(
+            "com.atproto.repo.createRecord",
+            &serde_json::json!({
+                "repo": repo,
+                "collection": BLOCK_COLLECTION,
+                "record": {
+                    "$type": BLOCK_COLLECTION,
+                    "subject": did,
+                    "createdAt": chrono::Utc::now().to_rfc3339()
+                }
+            }),
+        )
+        .await
+}
+
+/// Unfollows an account by deleting our follow record for it
+///
+/// Returns the deleted record, or `None` if we weren't following the account.
+pub async fn unfollow(client: &XrpcClient, did: &str) -> AppResult<Option<RecordRef>> {
+    let repo = client.session()?.did;
+    let Some(record) = find_follow_record(client, did).await? else {
+        return Ok(None);
+    };
+
+    let _: serde_json::Value = client
+        .procedure(
+            "com.atproto.repo.deleteRecord",
+            &serde_json::json!({
+                "repo": repo,
+                "collection": FOLLOW_COLLECTION,
+                "rkey": rkey_from_uri(&record.uri)?
+            }),
+        )
         .await?;
 
-    if !response.status().is_success() {
-        let error_text = response.text().await?;
-        error!("Failed to follow user: {}", error_text);
-        return Err(AppError::Api(error_text));
+    Ok(Some(record))
+}
+
+/// Looks up our follow record for `did`
+///
+/// The profile's viewer state is tried first; if the AppView doesn't know about
+/// the follow yet we fall back to scanning our own follow records.
+async fn find_follow_record(client: &XrpcClient, did: &str) -> AppResult<Option<RecordRef>> {
+    let repo = client.session()?.did;
+
+    match client
+        .query::<_, Profile>("app.bsky.actor.getProfile", &[("actor", did)])
+        .await
+    {
+        Ok(profile) => {
+            if let Some(uri) = profile.viewer.and_then(|viewer| viewer.following) {
+                let rkey = rkey_from_uri(&uri)?;
+                let record: RecordRef = client
+                    .query(
+                        "com.atproto.repo.getRecord",
+                        &[
+                            ("repo", repo.as_str()),
+                            ("collection", FOLLOW_COLLECTION),
+                            ("rkey", rkey),
+                        ],
+                    )
+                    .await?;
+                return Ok(Some(record));
+            }
+        }
+        Err(e) => warn!("Could not read viewer state for {}: {}", did, e),
     }
 
-    Ok(())
+    let mut cursor: Option<String> = None;
+    loop {
+        let mut params = vec![
+            ("repo", repo.clone()),
+            ("collection", FOLLOW_COLLECTION.to_string()),
+            ("limit", MAX_PAGE_SIZE.to_string()),
+        ];
+        if let Some(cursor) = &cursor {
+            params.push(("cursor", cursor.clone()));
+        }
+
+        let page: ListRecordsPage = client.query("com.atproto.repo.listRecords", &params).await?;
+        if let Some(record) = page
+            .records
+            .iter()
+            .find(|record| record.value.get("subject").and_then(|s| s.as_str()) == Some(did))
+        {
+            return Ok(Some(RecordRef {
+                uri: record.uri.clone(),
+                cid: record.cid.clone(),
+            }));
+        }
+
+        match page.cursor {
+            Some(next) if !page.records.is_empty() && cursor.as_ref() != Some(&next) => {
+                cursor = Some(next)
+            }
+            _ => return Ok(None),
+        }
+    }
 }
 
-pub async fn unfollow(did: &str) -> AppResult<()> {
-    let session = get_session()?;
-    let client = Client::new();
-    
-    let response = client
-        .post(format!("{}/app.bsky.graph.unfollow", BLUESKY_API_URL))
-        .header("Authorization", format!("Bearer {}", session.access_jwt))
-        .json(&serde_json::json!({
-            "subject": did,
-            "createdAt": chrono::Utc::now().to_rfc3339()
-        }))
-        .send()
+/// Turns a handle or DID into a DID, asking the PDS to resolve handles
+pub async fn resolve_actor_did(client: &XrpcClient, actor: &str) -> AppResult<String> {
+    let actor = normalize_actor(actor)?;
+    if actor.starts_with("did:") {
+        return Ok(actor);
+    }
+
+    #[derive(Deserialize)]
+    struct ResolveHandleResponse {
+        did: String,
+    }
+
+    let response: ResolveHandleResponse = client
+        .query("com.atproto.identity.resolveHandle", &[("handle", actor)])
         .await?;
+    Ok(response.did)
+}
+
+/// Extracts the record key from an `at://<repo>/<collection>/<rkey>` URI
+pub fn rkey_from_uri(uri: &str) -> AppResult<&str> {
+    let path = uri
+        .strip_prefix("at://")
+        .ok_or_else(|| AppError::InvalidInput(format!("Not an AT-URI: {}", uri)))?;
 
-    if !response.status().is_success() {
-        let error_text = response.text().await?;
-        error!("Failed to unfollow user: {}", error_text);
-        return Err(AppError::Api(error_text));
+    match path.split('/').collect::<Vec<_>>().as_slice() {
+        [_, _, rkey] if !rkey.is_empty() => Ok(rkey),
+        _ => Err(AppError::InvalidInput(format!("AT-URI has no record key: {}", uri))),
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    Ok(())
-} 
\ No newline at end of file
+    #[test]
+    fn test_graph_page_accepts_both_listings() {
+        let followers: GraphPage = serde_json::from_str(
+            r#"{"subject": {}, "followers": [{"did": "did:plc:a", "handle": "a.test"}], "cursor": "c1"}"#,
+        )
+        .unwrap();
+        assert_eq!(followers.items.len(), 1);
+        assert_eq!(followers.cursor.as_deref(), Some("c1"));
+
+        let follows: GraphPage = serde_json::from_str(
+            r#"{"subject": {}, "follows": [{"did": "did:plc:b", "handle": "b.test", "indexedAt": "2024-01-01T00:00:00Z"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(follows.items[0].handle, "b.test");
+        assert!(follows.items[0].indexed_at.is_some());
+        assert!(follows.cursor.is_none());
+    }
+
+    #[test]
+    fn test_rkey_from_uri() {
+        assert_eq!(
+            rkey_from_uri("at://did:plc:abc/app.bsky.graph.follow/3kabc").unwrap(),
+            "3kabc"
+        );
+        assert!(rkey_from_uri("https://example.com/x/y/z").is_err());
+        assert!(rkey_from_uri("at://did:plc:abc/app.bsky.graph.follow").is_err());
+    }
+}
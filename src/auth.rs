@@ -1,16 +1,14 @@
 use crate::error::{AppError, Result};
-use chrono::{DateTime, Utc};
-use directories::ProjectDirs;
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
-use std::io::Write;
-use crate::api::BLUESKY_API_URL;
-use crate::db::{save_handle, get_saved_handle};
-use rpassword::prompt_password;
-use log::{info, warn, error};
-use std::sync::{Arc, Mutex};
+use crate::client::{Endpoints, XrpcClient, DEFAULT_SERVICE_URL};
+use crate::identity::{DidDocument, IdentityResolver};
+use crate::config::data_dir;
+use crate::output::Render;
+use log::{info, warn};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
 use once_cell::sync::Lazy;
 
 /// Represents a BlueSky user session with authentication tokens and user information
@@ -21,152 +19,306 @@ pub struct Session {
     pub handle: String,
     pub did: String,
     pub email: String,
+    /// PDS endpoint discovered from the DID document at login
+    #[serde(default)]
+    pub service_endpoint: Option<String>,
 }
 
-/// Session manager to handle authentication state
+/// Every stored session, keyed by DID, plus the account commands act as
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SessionStore {
+    active: Option<String>,
+    #[serde(default)]
+    sessions: BTreeMap<String, Session>,
+    /// DID of the account imported from a single-account `session.json`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    imported: Option<String>,
+}
+
+impl SessionStore {
+    /// Reads `sessions.json`, importing a single-account `session.json` if present
+    fn load() -> Result<Self> {
+        let path = get_session_path()?;
+        if path.exists() {
+            let json = fs::read_to_string(path)?;
+            return Ok(serde_json::from_str(&json)?);
+        }
+
+        let mut store = Self::default();
+        let legacy_path = data_dir()?.join("session.json");
+        if legacy_path.exists() {
+            let session: Session = serde_json::from_str(&fs::read_to_string(&legacy_path)?)?;
+            info!("Importing session for {} from {}", session.handle, legacy_path.display());
+            store.active = Some(session.did.clone());
+            store.imported = Some(session.did.clone());
+            store.sessions.insert(session.did.clone(), session);
+            store.save()?;
+            fs::remove_file(legacy_path)?;
+        }
+        Ok(store)
+    }
+
+    fn save(&self) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(get_session_path()?, json)?;
+        Ok(())
+    }
+
+    /// Finds a stored session by DID or handle
+    fn find(&self, account: &str) -> Option<&Session> {
+        let account = account.trim().trim_start_matches('@');
+        self.sessions.get(account).or_else(|| {
+            self.sessions
+                .values()
+                .find(|session| session.handle.eq_ignore_ascii_case(account))
+        })
+    }
+
+    /// Session for `selected`, or for the active account when nothing is selected
+    fn current(&self, selected: Option<&str>) -> Result<&Session> {
+        match selected {
+            Some(account) => self
+                .find(account)
+                .ok_or_else(|| AppError::Session(format!("No session for account {}", account))),
+            None => self
+                .active
+                .as_ref()
+                .and_then(|did| self.sessions.get(did))
+                .ok_or_else(|| AppError::Session("Not logged in".to_string())),
+        }
+    }
+}
+
+/// Session manager to handle authentication state for every stored account
 #[derive(Debug)]
 pub struct SessionManager {
-    session: Arc<Mutex<Option<Session>>>,
+    store: Mutex<Option<SessionStore>>,
+    selected: Mutex<Option<String>>,
 }
 
 impl SessionManager {
     pub fn new() -> Self {
         Self {
-            session: Arc::new(Mutex::new(None)),
+            store: Mutex::new(None),
+            selected: Mutex::new(None),
         }
     }
 
-    pub fn get_session(&self) -> Result<Session> {
-        let mut session_guard = self.session.lock()
+    /// Runs `f` against the store, loading it on first use and, if `save` is
+    /// set, writing it back once `f` succeeds
+    fn with_store<T>(
+        &self,
+        save: bool,
+        f: impl FnOnce(&mut SessionStore, Option<&str>) -> Result<T>,
+    ) -> Result<T> {
+        let mut store_guard = self.store.lock()
             .map_err(|_| AppError::Auth("Failed to lock session".to_string()))?;
-        
-        if session_guard.is_none() {
-            let session_path = get_session_path()?;
-            let json = fs::read_to_string(session_path)?;
-            let session: Session = serde_json::from_str(&json)?;
-            *session_guard = Some(session.clone());
-            Ok(session)
-        } else {
-            Ok(session_guard.as_ref().unwrap().clone())
+        let selected = self.selected.lock()
+            .map_err(|_| AppError::Auth("Failed to lock session".to_string()))?
+            .clone();
+
+        if store_guard.is_none() {
+            *store_guard = Some(SessionStore::load()?);
         }
+        let store = store_guard.as_mut().unwrap();
+        let result = f(store, selected.as_deref())?;
+        if save {
+            store.save()?;
+        }
+        Ok(result)
+    }
+
+    /// Makes later calls act as `account` (a DID or handle) instead of the active one
+    pub fn select(&self, account: &str) -> Result<()> {
+        *self.selected.lock()
+            .map_err(|_| AppError::Auth("Failed to lock session".to_string()))? = Some(account.to_string());
+        Ok(())
+    }
+
+    pub fn get_session(&self) -> Result<Session> {
+        self.with_store(false, |store, selected| Ok(store.current(selected)?.clone()))
     }
 
+    /// Stores `session` under its DID; the first account saved becomes active
     pub fn save_session(&self, session: &Session) -> Result<()> {
-        let session_path = get_session_path()?;
-        let json = serde_json::to_string_pretty(session)?;
-        fs::write(session_path, json)?;
-        *self.session.lock()
-            .map_err(|_| AppError::Auth("Failed to lock session".to_string()))? = Some(session.clone());
+        self.with_store(true, |store, _| {
+            store.active.get_or_insert_with(|| session.did.clone());
+            store.sessions.insert(session.did.clone(), session.clone());
+            Ok(())
+        })?;
         info!("Session saved successfully");
         Ok(())
     }
 
+    /// Removes the current account's session
     pub fn clear_session(&self) -> Result<()> {
-        let session_path = get_session_path()?;
-        if session_path.exists() {
-            fs::remove_file(session_path)?;
-            *self.session.lock()
-                .map_err(|_| AppError::Auth("Failed to lock session".to_string()))? = None;
-            info!("Successfully logged out");
-        } else {
-            warn!("No active session found");
-        }
+        let did = match self.get_session() {
+            Ok(session) => session.did,
+            Err(_) => {
+                warn!("No active session found");
+                return Ok(());
+            }
+        };
+        self.remove(&did)?;
+        info!("Successfully logged out");
         Ok(())
     }
+
+    pub fn list(&self) -> Result<Vec<(Session, bool)>> {
+        self.with_store(false, |store, _| {
+            Ok(store
+                .sessions
+                .values()
+                .map(|session| (session.clone(), store.active.as_ref() == Some(&session.did)))
+                .collect())
+        })
+    }
+
+    /// DID of the account that used this tool before multi-account support
+    ///
+    /// That is the account imported from `session.json`, or else the stored
+    /// account whose handle is `legacy_handle`.
+    pub fn legacy_owner(&self, legacy_handle: Option<&str>) -> Result<Option<String>> {
+        self.with_store(false, |store, _| {
+            Ok(store.imported.clone().or_else(|| {
+                legacy_handle
+                    .and_then(|handle| store.find(handle))
+                    .map(|session| session.did.clone())
+            }))
+        })
+    }
+
+    /// Makes `account` the active account, dropping any account picked with `select`
+    pub fn switch(&self, account: &str) -> Result<Session> {
+        let session = self.with_store(true, |store, _| {
+            let session = store.current(Some(account))?.clone();
+            store.active = Some(session.did.clone());
+            Ok(session)
+        })?;
+        *self.selected.lock()
+            .map_err(|_| AppError::Auth("Failed to lock session".to_string()))? = None;
+        Ok(session)
+    }
+
+    /// Forgets `account`; if it was active, another stored account takes over
+    pub fn remove(&self, account: &str) -> Result<Session> {
+        self.with_store(true, |store, _| {
+            let session = store.current(Some(account))?.clone();
+            store.sessions.remove(&session.did);
+            if store.active.as_ref() == Some(&session.did) {
+                store.active = store.sessions.keys().next().cloned();
+            }
+            Ok(session)
+        })
+    }
+}
+
+impl Default for SessionManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 // Global session manager instance
-static SESSION_MANAGER: Lazy<SessionManager> = Lazy::new(|| SessionManager::new());
+static SESSION_MANAGER: Lazy<SessionManager> = Lazy::new(SessionManager::new);
 
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 struct LoginRequest {
     identifier: String,
     password: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    app_password: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    verification_code: Option<String>,
+    auth_factor_token: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct LoginResponse {
     access_jwt: String,
     refresh_jwt: String,
     handle: String,
     did: String,
+    did_doc: Option<serde_json::Value>,
 }
 
 pub fn get_session_path() -> Result<PathBuf> {
-    let proj_dirs = ProjectDirs::from("com", "rusty-tools", "bsky")
-        .ok_or_else(|| AppError::Config("Failed to get project directories".to_string()))?;
-    
-    let data_dir = proj_dirs.data_dir();
-    fs::create_dir_all(data_dir)
-        .map_err(|e| AppError::Io(e))?;
-    
-    Ok(data_dir.join("session.json"))
+    Ok(data_dir()?.join("sessions.json"))
 }
 
-pub async fn login(handle: &str) -> Result<Session> {
+/// Creates a session with an app password and makes it the active account
+///
+/// Logs in through the configured service, or the PDS the handle resolves to.
+/// Accounts with email 2FA fail with [`XrpcErrorKind::AuthFactorTokenRequired`]
+/// until the emailed code is passed as `auth_factor_token`.
+///
+/// [`XrpcErrorKind::AuthFactorTokenRequired`]: crate::error::XrpcErrorKind::AuthFactorTokenRequired
+pub async fn create_session(
+    handle: &str,
+    password: &str,
+    auth_factor_token: Option<&str>,
+    endpoints: &Endpoints,
+) -> Result<Session> {
     info!("Attempting login for handle: {}", handle);
-    let password = prompt_password("Enter your app password: ")?;
-    let password_clone = password.clone();
-
-    let client = Client::new();
-    let mut response = client
-        .post(format!("{}/com.atproto.server.createSession", BLUESKY_API_URL))
-        .json(&LoginRequest {
-            identifier: handle.to_string(),
-            password: password_clone.clone(),
-            app_password: Some(password_clone),
-            verification_code: None,
-        })
-        .send()
-        .await?;
+    let service = match &endpoints.service {
+        Some(service) => service.clone(),
+        None => discover_pds(handle, endpoints).await,
+    };
 
-    // Handle email verification if required
-    if !response.status().is_success() {
-        let error_text = response.text().await?;
-        if error_text.contains("AuthFactorTokenRequired") {
-            let verification_code = prompt_password("Enter the verification code sent to your email: ")?;
-            
-            response = client
-                .post(format!("{}/com.atproto.server.createSession", BLUESKY_API_URL))
-                .json(&LoginRequest {
-                    identifier: handle.to_string(),
-                    password: password.clone(),
-                    app_password: Some(password),
-                    verification_code: Some(verification_code),
-                })
-                .send()
-                .await?;
-        } else {
-            error!("Login failed: {}", error_text);
-            return Err(AppError::Auth(error_text));
-        }
-    }
+    let client = XrpcClient::new(&service)?;
+    let login_response: LoginResponse = client
+        .procedure(
+            "com.atproto.server.createSession",
+            &LoginRequest {
+                identifier: handle.to_string(),
+                password: password.to_string(),
+                auth_factor_token: auth_factor_token.map(str::to_string),
+            },
+        )
+        .await?;
 
-    if !response.status().is_success() {
-        let error_text = response.text().await?;
-        error!("Login failed: {}", error_text);
-        return Err(AppError::Auth(error_text));
-    }
+    // Talk to the account's own PDS from now on, falling back to the login service
+    let service_endpoint = login_response
+        .did_doc
+        .and_then(|did_doc| serde_json::from_value::<DidDocument>(did_doc).ok())
+        .and_then(|did_doc| did_doc.pds_endpoint().map(str::to_string))
+        .unwrap_or(service);
 
-    let login_response: LoginResponse = response.json().await?;
-    
     let session = Session {
         access_jwt: login_response.access_jwt,
         refresh_jwt: login_response.refresh_jwt,
         handle: login_response.handle,
         did: login_response.did,
         email: String::new(), // TODO: Get email from API if available
+        service_endpoint: Some(service_endpoint),
     };
     
     SESSION_MANAGER.save_session(&session)?;
+    SESSION_MANAGER.switch(&session.did)?;
     info!("Successfully logged in as {}", session.handle);
     Ok(session)
 }
 
+/// Finds the PDS hosting `handle`, falling back to [`DEFAULT_SERVICE_URL`]
+async fn discover_pds(handle: &str, endpoints: &Endpoints) -> String {
+    let discovered = match IdentityResolver::for_endpoints(endpoints) {
+        Ok(resolver) => resolver.resolve(handle).await,
+        Err(e) => Err(e),
+    };
+
+    match discovered {
+        Ok(did_doc) if did_doc.pds_endpoint().is_some() => {
+            let pds = did_doc.pds_endpoint().unwrap_or(DEFAULT_SERVICE_URL);
+            info!("Resolved {} to PDS {}", handle, pds);
+            pds.to_string()
+        }
+        Ok(_) => DEFAULT_SERVICE_URL.to_string(),
+        Err(e) => {
+            warn!("Could not resolve PDS for {}: {}", handle, e);
+            DEFAULT_SERVICE_URL.to_string()
+        }
+    }
+}
+
 pub fn logout() -> Result<()> {
     SESSION_MANAGER.clear_session()
 }
@@ -175,34 +327,83 @@ pub fn get_session() -> Result<Session> {
     SESSION_MANAGER.get_session()
 }
 
-pub fn get_handle() -> Result<String> {
-    // First try to get the handle from the database
-    match get_saved_handle() {
-        Ok(Some(handle)) => {
-            info!("Using saved handle: {}", handle);
-            Ok(handle)
-        }
-        Ok(None) => {
-            // If not found in database, prompt the user
-            print!("Enter your BlueSky handle (e.g. user.bsky.social): ");
-            std::io::stdout().flush()
-                .map_err(AppError::Io)?;
-            
-            let mut handle = String::new();
-            std::io::stdin().read_line(&mut handle)
-                .map_err(AppError::Io)?;
-            let handle = handle.trim().to_string();
-
-            // Save the handle to the database for future use
-            save_handle(&handle)?;
-            info!("Saved new handle: {}", handle);
-
-            Ok(handle)
+pub fn save_session(session: &Session) -> Result<()> {
+    SESSION_MANAGER.save_session(session)
+}
+
+/// Acts as `account` (a DID or handle) for the rest of this run
+pub fn select_account(account: &str) -> Result<()> {
+    SESSION_MANAGER.select(account)
+}
+
+/// DID that owns data written before multi-account support, if it is known
+///
+/// `legacy_handle` is the handle the original `handles` table saved.
+pub fn legacy_owner(legacy_handle: Option<&str>) -> Result<Option<String>> {
+    SESSION_MANAGER.legacy_owner(legacy_handle)
+}
+
+/// An account with a stored session, as shown by `accounts list`
+#[derive(Debug, Serialize)]
+pub struct StoredAccount {
+    pub did: String,
+    pub handle: String,
+    pub service: Option<String>,
+    pub active: bool,
+}
+
+/// Result of the `accounts list` command
+#[derive(Debug, Serialize)]
+pub struct AccountList {
+    pub accounts: Vec<StoredAccount>,
+}
+
+impl Render for AccountList {
+    fn text(&self) -> String {
+        if self.accounts.is_empty() {
+            return "No accounts logged in".to_string();
         }
-        Err(e) => Err(e.into()),
+        self.accounts
+            .iter()
+            .map(|account| {
+                let marker = if account.active { "*" } else { " " };
+                format!("{} @{} ({})", marker, account.handle, account.did)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn records(&self) -> Result<Vec<serde_json::Value>> {
+        self.accounts
+            .iter()
+            .map(|account| Ok(serde_json::to_value(account)?))
+            .collect()
     }
 }
 
+/// Stored accounts, with the active one flagged
+pub fn list_accounts() -> Result<AccountList> {
+    let accounts = SESSION_MANAGER
+        .list()?
+        .into_iter()
+        .map(|(session, active)| StoredAccount {
+            did: session.did,
+            handle: session.handle,
+            service: session.service_endpoint,
+            active,
+        })
+        .collect();
+    Ok(AccountList { accounts })
+}
+
+pub fn switch_account(account: &str) -> Result<Session> {
+    SESSION_MANAGER.switch(account)
+}
+
+pub fn remove_account(account: &str) -> Result<Session> {
+    SESSION_MANAGER.remove(account)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -226,6 +427,7 @@ mod tests {
             handle: "test.bsky.social".to_string(),
             did: "test_did".to_string(),
             email: "test@example.com".to_string(),
+            service_endpoint: None,
         };
 
         // Test saving and retrieving session
@@ -239,11 +441,37 @@ mod tests {
         assert!(manager.get_session().is_err());
     }
 
+    fn session(did: &str, handle: &str) -> Session {
+        Session {
+            access_jwt: format!("{}-access", did),
+            refresh_jwt: format!("{}-refresh", did),
+            handle: handle.to_string(),
+            did: did.to_string(),
+            email: String::new(),
+            service_endpoint: None,
+        }
+    }
+
+    #[test]
+    fn test_session_store_lookup() {
+        let mut store = SessionStore::default();
+        for (did, handle) in [("did:plc:a", "brand-a.test"), ("did:plc:b", "brand-b.test")] {
+            store.sessions.insert(did.to_string(), session(did, handle));
+        }
+        assert!(store.current(None).is_err());
+
+        store.active = Some("did:plc:a".to_string());
+        assert_eq!(store.current(None).unwrap().did, "did:plc:a");
+        assert_eq!(store.current(Some("@Brand-B.test")).unwrap().did, "did:plc:b");
+        assert_eq!(store.current(Some("did:plc:b")).unwrap().handle, "brand-b.test");
+        assert!(store.current(Some("brand-c.test")).is_err());
+    }
+
     #[test]
     fn test_get_session_path() {
         let _temp_dir = setup_test_env();
         let path = get_session_path().unwrap();
         assert!(path.to_string_lossy().contains("bsky"));
-        assert!(path.to_string_lossy().contains("session.json"));
+        assert!(path.to_string_lossy().contains("sessions.json"));
     }
 } 
\ No newline at end of file
@@ -0,0 +1,290 @@
+use crate::auth::{get_session, save_session, Session};
+use crate::error::{AppError, RateLimitInfo, Result, XrpcError, XrpcErrorKind};
+use crate::ratelimit::{backoff, RateLimiter, WriteBudget};
+use crate::utils::rate_limit;
+use log::{debug, info, warn};
+use reqwest::{Client, Method, RequestBuilder};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+
+/// User agent sent with every XRPC request
+pub const USER_AGENT: &str = concat!("bsky-rusty-tools/", env!("CARGO_PKG_VERSION"));
+
+/// How often a request is retried after the server answers 429
+const MAX_RATE_LIMIT_RETRIES: u32 = 5;
+
+/// Service used when neither the user nor the session names one
+pub const DEFAULT_SERVICE_URL: &str = "https://bsky.social";
+
+/// Service URLs chosen on the command line or through the environment
+#[derive(Debug, Clone, Default)]
+pub struct Endpoints {
+    /// PDS (or entryway) that receives logins, writes and, by default, reads
+    pub service: Option<String>,
+    /// AppView that serves `app.bsky.*` reads, proxied through the PDS when logged in
+    pub appview: Option<String>,
+    /// PLC directory used to resolve `did:plc` identifiers
+    pub plc_directory: Option<String>,
+}
+
+/// Shared XRPC client owning the HTTP connection pool, base URL and session
+#[derive(Debug, Clone)]
+pub struct XrpcClient {
+    http: Client,
+    base_url: String,
+    appview_url: Option<String>,
+    /// `atproto-proxy` target naming the AppView for authenticated reads
+    appview_proxy: Option<String>,
+    session: Arc<RwLock<Option<Session>>>,
+    limiter: Arc<RateLimiter>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RefreshResponse {
+    access_jwt: String,
+    refresh_jwt: String,
+    handle: String,
+    did: String,
+}
+
+impl XrpcClient {
+    /// Creates an unauthenticated client for the service at `service_url`
+    pub fn new(service_url: &str) -> Result<Self> {
+        let http = Client::builder()
+            .user_agent(USER_AGENT)
+            .gzip(true)
+            .build()?;
+
+        Ok(Self {
+            http,
+            base_url: xrpc_url(service_url),
+            appview_url: None,
+            appview_proxy: None,
+            session: Arc::new(RwLock::new(None)),
+            limiter: Arc::new(RateLimiter::default()),
+        })
+    }
+
+    /// Creates a client using the stored session
+    ///
+    /// The service is taken from `endpoints`, then from the PDS discovered at
+    /// login, then falls back to [`DEFAULT_SERVICE_URL`].
+    pub fn authenticated(endpoints: &Endpoints) -> Result<Self> {
+        let session = get_session()?;
+        let service = endpoints
+            .service
+            .clone()
+            .or_else(|| session.service_endpoint.clone())
+            .unwrap_or_else(|| DEFAULT_SERVICE_URL.to_string());
+
+        Ok(Self::new(&service)?
+            .with_appview(endpoints.appview.as_deref())
+            .with_session(session))
+    }
+
+    /// Sends `app.bsky.*` queries to a separate AppView instead of the PDS's default
+    ///
+    /// Logged-in reads still go through the PDS, which proxies them to the
+    /// AppView so responses keep our viewer state (follows, blocks).
+    pub fn with_appview(mut self, appview_url: Option<&str>) -> Self {
+        self.appview_url = appview_url.map(xrpc_url);
+        self.appview_proxy = appview_url.map(appview_proxy);
+        self
+    }
+
+    /// Replaces the default per-account write budget
+    pub fn with_write_budget(mut self, budget: WriteBudget) -> Self {
+        self.limiter = Arc::new(RateLimiter::new(budget));
+        self
+    }
+
+    /// Attaches a session whose access token is sent with every request
+    pub fn with_session(self, session: Session) -> Self {
+        *self.session.write().unwrap_or_else(|e| e.into_inner()) = Some(session);
+        self
+    }
+
+    /// Returns a copy of the session attached to this client
+    pub fn session(&self) -> Result<Session> {
+        self.session
+            .read()
+            .map_err(|_| AppError::Session("Failed to lock session".to_string()))?
+            .clone()
+            .ok_or_else(|| AppError::Session("Not logged in".to_string()))
+    }
+
+    /// Calls an XRPC query (HTTP GET) with the given query parameters
+    pub async fn query<P, T>(&self, nsid: &str, params: &P) -> Result<T>
+    where
+        P: Serialize + ?Sized,
+        T: DeserializeOwned,
+    {
+        self.call(nsid, || self.request(Method::GET, nsid).query(params))
+            .await
+    }
+
+    /// Calls an XRPC procedure (HTTP POST) with the given JSON body
+    pub async fn procedure<B, T>(&self, nsid: &str, body: &B) -> Result<T>
+    where
+        B: Serialize + ?Sized,
+        T: DeserializeOwned,
+    {
+        self.call(nsid, || self.request(Method::POST, nsid).json(body))
+            .await
+    }
+
+    /// Exchanges the refresh token for a new token pair and persists it
+    pub async fn refresh_session(&self) -> Result<Session> {
+        let current = self.session()?;
+        info!("Refreshing access token for {}", current.handle);
+
+        let request = self
+            .http
+            .post(format!("{}/com.atproto.server.refreshSession", self.base_url))
+            .bearer_auth(&current.refresh_jwt);
+        let refreshed: RefreshResponse = self
+            .send("com.atproto.server.refreshSession", request)
+            .await
+            .inspect_err(|e| warn!("Failed to refresh session: {}", e))?;
+
+        let session = Session {
+            access_jwt: refreshed.access_jwt,
+            refresh_jwt: refreshed.refresh_jwt,
+            handle: refreshed.handle,
+            did: refreshed.did,
+            email: current.email,
+            service_endpoint: current.service_endpoint,
+        };
+        save_session(&session)?;
+        *self
+            .session
+            .write()
+            .map_err(|_| AppError::Session("Failed to lock session".to_string()))? = Some(session.clone());
+        Ok(session)
+    }
+
+    /// Sends a request through the rate limiter
+    ///
+    /// An ExpiredToken answer refreshes the session and retries once; a 429
+    /// backs off with jitter and retries up to [`MAX_RATE_LIMIT_RETRIES`] times.
+    async fn call<T, F>(&self, nsid: &str, build: F) -> Result<T>
+    where
+        T: DeserializeOwned,
+        F: Fn() -> RequestBuilder,
+    {
+        let mut refreshed = false;
+        let mut attempt = 0;
+        loop {
+            self.limiter.acquire(&self.account_key(), nsid).await?;
+            match self.send(nsid, build()).await {
+                Err(AppError::Xrpc(e))
+                    if e.kind == XrpcErrorKind::ExpiredToken && !refreshed && self.session().is_ok() =>
+                {
+                    refreshed = true;
+                    self.refresh_session().await?;
+                }
+                Err(AppError::Xrpc(e))
+                    if e.kind == XrpcErrorKind::RateLimitExceeded && attempt < MAX_RATE_LIMIT_RETRIES =>
+                {
+                    let delay = backoff(attempt, &e.rate_limit);
+                    warn!("{} rate limited, retrying in {:.1}s", nsid, delay.as_secs_f64());
+                    attempt += 1;
+                    rate_limit(delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Key under which the rate limiter tracks this client's budget
+    fn account_key(&self) -> String {
+        self.session()
+            .map(|session| session.did)
+            .unwrap_or_else(|_| "anonymous".to_string())
+    }
+
+    fn request(&self, method: Method, nsid: &str) -> RequestBuilder {
+        let appview_read = method == Method::GET && nsid.starts_with("app.bsky.");
+        let session = self.session();
+
+        // Our access token is only valid at the PDS, so anonymous reads are the
+        // only ones sent to the AppView directly
+        if let (Some(appview_url), true, Err(_)) = (&self.appview_url, appview_read, &session) {
+            return self.http.request(method, format!("{}/{}", appview_url, nsid));
+        }
+
+        let mut request = self
+            .http
+            .request(method, format!("{}/{}", self.base_url, nsid));
+        if let (Some(proxy), true) = (&self.appview_proxy, appview_read) {
+            request = request.header("atproto-proxy", proxy);
+        }
+
+        match session {
+            Ok(session) => request.bearer_auth(session.access_jwt),
+            Err(_) => request,
+        }
+    }
+
+    async fn send<T: DeserializeOwned>(&self, nsid: &str, request: RequestBuilder) -> Result<T> {
+        debug!("XRPC {}", nsid);
+        let response = request.send().await?;
+        self.limiter
+            .record(&self.account_key(), RateLimitInfo::from_headers(response.headers()));
+
+        let status = response.status();
+        if !status.is_success() {
+            let headers = response.headers().clone();
+            let body = response.text().await?;
+            let xrpc_error = XrpcError::from_response(status.as_u16(), &headers, &body);
+            debug!("{} failed: {}", nsid, xrpc_error);
+            return Err(xrpc_error.into());
+        }
+
+        // Some procedures answer with an empty body; treat that as JSON null
+        let bytes = response.bytes().await?;
+        let body: &[u8] = if bytes.is_empty() { b"null" } else { &bytes };
+        Ok(serde_json::from_slice(body)?)
+    }
+}
+
+/// Turns a service origin such as `https://bsky.social` into its XRPC base URL
+fn xrpc_url(service_url: &str) -> String {
+    let service_url = service_url.trim_end_matches('/');
+    let service_url = service_url.strip_suffix("/xrpc").unwrap_or(service_url);
+    format!("{}/xrpc", service_url)
+}
+
+/// The `atproto-proxy` value for the AppView at `appview_url`, named by its `did:web`
+fn appview_proxy(appview_url: &str) -> String {
+    let host = appview_url
+        .split_once("://")
+        .map_or(appview_url, |(_, rest)| rest)
+        .split('/')
+        .next()
+        .unwrap_or_default();
+    format!("did:web:{}#bsky_appview", host.replace(':', "%3A"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_appview_proxy() {
+        assert_eq!(appview_proxy("https://api.bsky.app"), "did:web:api.bsky.app#bsky_appview");
+        assert_eq!(
+            appview_proxy("http://localhost:2584/xrpc"),
+            "did:web:localhost%3A2584#bsky_appview"
+        );
+    }
+
+    #[test]
+    fn test_xrpc_url() {
+        assert_eq!(xrpc_url("https://bsky.social"), "https://bsky.social/xrpc");
+        assert_eq!(xrpc_url("https://pds.example.com/"), "https://pds.example.com/xrpc");
+        assert_eq!(xrpc_url("http://localhost:2583/xrpc"), "http://localhost:2583/xrpc");
+    }
+}
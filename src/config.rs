@@ -0,0 +1,366 @@
+use crate::client::Endpoints;
+use crate::error::{AppError, Result};
+use crate::output::{OutputFormat, Render};
+use crate::ratelimit::WriteBudget;
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Profile used when neither `--profile` nor the config file picks one
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Follows per minute used by `mirror-follows` when nothing else is set
+pub const DEFAULT_PER_MINUTE: u32 = 30;
+
+/// Keys accepted by `config set`
+pub const KEYS: &[&str] = &[
+    "handle",
+    "service",
+    "appview",
+    "plc_directory",
+    "db_path",
+    "output",
+    "per_minute",
+    "points_per_hour",
+    "points_per_day",
+];
+
+fn project_dirs() -> Result<ProjectDirs> {
+    ProjectDirs::from("com", "rusty-tools", "bsky")
+        .ok_or_else(|| AppError::Config("Failed to get project directories".to_string()))
+}
+
+/// Directory holding the session and the default database, created on demand
+pub fn data_dir() -> Result<PathBuf> {
+    let data_dir = project_dirs()?.data_dir().to_path_buf();
+    fs::create_dir_all(&data_dir)?;
+    Ok(data_dir)
+}
+
+/// Location of `config.toml` in the platform config directory
+pub fn default_config_path() -> Result<PathBuf> {
+    Ok(project_dirs()?.config_dir().join("config.toml"))
+}
+
+/// Settings stored for one named profile; unset values fall back to defaults
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub handle: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub appview: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plc_directory: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub db_path: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<OutputFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub per_minute: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub points_per_hour: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub points_per_day: Option<u32>,
+}
+
+impl ProfileConfig {
+    /// Parses `value` for `key` and stores it
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let text = || Some(value.to_string());
+        let number = || match value.parse::<u32>() {
+            Ok(0) => Err(AppError::InvalidInput(format!("{} must be greater than zero", key))),
+            Ok(number) => Ok(Some(number)),
+            Err(_) => Err(AppError::InvalidInput(format!("{} must be a number, got {}", key, value))),
+        };
+
+        match key {
+            "handle" => self.handle = text(),
+            "service" => self.service = text(),
+            "appview" => self.appview = text(),
+            "plc_directory" => self.plc_directory = text(),
+            "db_path" => self.db_path = Some(PathBuf::from(value)),
+            "output" => {
+                self.output = Some(
+                    value
+                        .parse()
+                        .map_err(|_| AppError::InvalidInput(format!("Unknown output format: {}", value)))?,
+                )
+            }
+            "per_minute" => self.per_minute = number()?,
+            "points_per_hour" => self.points_per_hour = number()?,
+            "points_per_day" => self.points_per_day = number()?,
+            _ => {
+                return Err(AppError::InvalidInput(format!(
+                    "Unknown config key {} (expected one of: {})",
+                    key,
+                    KEYS.join(", ")
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    /// Rejects zero rates and budgets, which would stall every write
+    fn validate(&self, profile: &str) -> Result<()> {
+        for (key, value) in [
+            ("per_minute", self.per_minute),
+            ("points_per_hour", self.points_per_hour),
+            ("points_per_day", self.points_per_day),
+        ] {
+            if value == Some(0) {
+                return Err(AppError::InvalidInput(format!(
+                    "{} of profile {} must be greater than zero",
+                    key, profile
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Fills every unset value from `fallback`
+    fn or(self, fallback: ProfileConfig) -> Self {
+        Self {
+            handle: self.handle.or(fallback.handle),
+            service: self.service.or(fallback.service),
+            appview: self.appview.or(fallback.appview),
+            plc_directory: self.plc_directory.or(fallback.plc_directory),
+            db_path: self.db_path.or(fallback.db_path),
+            output: self.output.or(fallback.output),
+            per_minute: self.per_minute.or(fallback.per_minute),
+            points_per_hour: self.points_per_hour.or(fallback.points_per_hour),
+            points_per_day: self.points_per_day.or(fallback.points_per_day),
+        }
+    }
+}
+
+/// Contents of `config.toml`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, ProfileConfig>,
+}
+
+impl Config {
+    /// Reads the config file, treating a missing file as empty
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = fs::read_to_string(path)?;
+        toml::from_str(&contents)
+            .map_err(|e| AppError::Config(format!("Invalid {}: {}", path.display(), e)))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let contents = toml::to_string_pretty(self)
+            .map_err(|e| AppError::Config(format!("Failed to write config: {}", e)))?;
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    /// Name of the profile to use when none is given on the command line
+    pub fn profile_name(&self) -> &str {
+        self.default_profile.as_deref().unwrap_or(DEFAULT_PROFILE)
+    }
+}
+
+/// The effective settings after layering flags and env vars over the profile
+#[derive(Debug, Clone, Serialize)]
+pub struct Settings {
+    pub profile: String,
+    pub config_path: PathBuf,
+    pub handle: Option<String>,
+    pub service: Option<String>,
+    pub appview: Option<String>,
+    pub plc_directory: Option<String>,
+    pub db_path: PathBuf,
+    pub output: OutputFormat,
+    pub per_minute: u32,
+    pub points_per_hour: u32,
+    pub points_per_day: u32,
+}
+
+impl Settings {
+    /// Resolves `profile` from `config`, letting set values in `overrides` win
+    ///
+    /// `overrides` carries the command-line flags; clap has already folded the
+    /// matching `BSKY_*` environment variables into them.
+    pub fn resolve(
+        config: &Config,
+        config_path: PathBuf,
+        profile: Option<String>,
+        overrides: ProfileConfig,
+    ) -> Result<Self> {
+        let profile = profile.unwrap_or_else(|| config.profile_name().to_string());
+        let stored = match config.profiles.get(&profile) {
+            Some(stored) => stored.clone(),
+            None if profile == config.profile_name() => ProfileConfig::default(),
+            None => {
+                return Err(AppError::Config(format!(
+                    "No profile named {} in {}",
+                    profile,
+                    config_path.display()
+                )))
+            }
+        };
+        let merged = overrides.or(stored);
+        merged.validate(&profile)?;
+        let budget = WriteBudget::default();
+
+        Ok(Self {
+            db_path: match merged.db_path {
+                Some(db_path) => db_path,
+                None => data_dir()?.join("followers.db"),
+            },
+            handle: merged.handle,
+            service: merged.service,
+            appview: merged.appview,
+            plc_directory: merged.plc_directory,
+            output: merged.output.unwrap_or_default(),
+            per_minute: merged.per_minute.unwrap_or(DEFAULT_PER_MINUTE),
+            points_per_hour: merged.points_per_hour.unwrap_or(budget.points_per_hour),
+            points_per_day: merged.points_per_day.unwrap_or(budget.points_per_day),
+            profile,
+            config_path,
+        })
+    }
+
+    pub fn endpoints(&self) -> Endpoints {
+        Endpoints {
+            service: self.service.clone(),
+            appview: self.appview.clone(),
+            plc_directory: self.plc_directory.clone(),
+        }
+    }
+
+    pub fn write_budget(&self) -> WriteBudget {
+        WriteBudget {
+            points_per_hour: self.points_per_hour,
+            points_per_day: self.points_per_day,
+        }
+    }
+}
+
+impl Render for Settings {
+    fn text(&self) -> String {
+        let unset = || "(unset)".to_string();
+        [
+            ("profile", self.profile.clone()),
+            ("config_path", self.config_path.display().to_string()),
+            ("handle", self.handle.clone().unwrap_or_else(unset)),
+            ("service", self.service.clone().unwrap_or_else(unset)),
+            ("appview", self.appview.clone().unwrap_or_else(unset)),
+            ("plc_directory", self.plc_directory.clone().unwrap_or_else(unset)),
+            ("db_path", self.db_path.display().to_string()),
+            ("output", self.output.to_string()),
+            ("per_minute", self.per_minute.to_string()),
+            ("points_per_hour", self.points_per_hour.to_string()),
+            ("points_per_day", self.points_per_day.to_string()),
+        ]
+        .iter()
+        .map(|(key, value)| format!("{} = {}", key, value))
+        .collect::<Vec<_>>()
+        .join("\n")
+    }
+}
+
+/// Stores `key = value` in `profile` of the config file at `path`
+pub fn set_value(path: &Path, profile: &str, key: &str, value: &str) -> Result<()> {
+    let mut config = Config::load(path)?;
+    config
+        .profiles
+        .entry(profile.to_string())
+        .or_default()
+        .set(key, value)?;
+    config.save(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_profiles() {
+        let config: Config = toml::from_str(
+            r#"
+            default_profile = "work"
+
+            [profiles.work]
+            handle = "me.work.test"
+            service = "https://pds.work.test"
+            output = "json"
+            per_minute = 10
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.profile_name(), "work");
+        let work = &config.profiles["work"];
+        assert_eq!(work.handle.as_deref(), Some("me.work.test"));
+        assert_eq!(work.output, Some(OutputFormat::Json));
+        assert_eq!(work.per_minute, Some(10));
+
+        assert!(toml::from_str::<Config>("[profiles.x]\nhandel = \"typo\"").is_err());
+    }
+
+    #[test]
+    fn test_overrides_win_over_profile() {
+        let mut config = Config::default();
+        let mut stored = ProfileConfig::default();
+        stored.set("handle", "stored.test").unwrap();
+        stored.set("service", "https://stored.test").unwrap();
+        stored.set("db_path", "/tmp/stored.db").unwrap();
+        config.profiles.insert(DEFAULT_PROFILE.to_string(), stored);
+
+        let overrides = ProfileConfig {
+            service: Some("https://flag.test".to_string()),
+            ..Default::default()
+        };
+        let settings = Settings::resolve(&config, PathBuf::from("config.toml"), None, overrides).unwrap();
+
+        assert_eq!(settings.handle.as_deref(), Some("stored.test"));
+        assert_eq!(settings.service.as_deref(), Some("https://flag.test"));
+        assert_eq!(settings.db_path, PathBuf::from("/tmp/stored.db"));
+        assert_eq!(settings.per_minute, DEFAULT_PER_MINUTE);
+
+        let missing = Settings::resolve(
+            &config,
+            PathBuf::from("config.toml"),
+            Some("nope".to_string()),
+            ProfileConfig::default(),
+        );
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn test_zero_budget_in_file_is_rejected() {
+        let config: Config = toml::from_str("[profiles.default]\npoints_per_day = 0").unwrap();
+        let resolved = Settings::resolve(&config, PathBuf::from("config.toml"), None, ProfileConfig::default());
+        assert!(matches!(resolved, Err(AppError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_set_validates_values() {
+        let mut profile = ProfileConfig::default();
+        assert!(profile.set("per_minute", "fast").is_err());
+        assert!(profile.set("output", "xml").is_err());
+        assert!(profile.set("colour", "blue").is_err());
+
+        assert!(profile.set("points_per_hour", "0").is_err());
+        assert_eq!(profile.points_per_hour, None);
+
+        profile.set("output", "CSV").unwrap();
+        assert_eq!(profile.output, Some(OutputFormat::Csv));
+    }
+}
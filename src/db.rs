@@ -1,109 +1,235 @@
 use anyhow::{Result, Context};
 use chrono::{DateTime, Utc};
-use directories::ProjectDirs;
-use rusqlite::{Connection, params, OptionalExtension};
+use rusqlite::{Connection, OpenFlags, params, OptionalExtension};
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::PathBuf;
-use crate::auth::get_session;
-use crate::api::BLUESKY_API_URL;
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
-use std::sync::Mutex;
+use crate::api::{get_followers, get_following, Follower, GraphKind, PageOptions, Profile};
+use crate::client::XrpcClient;
+use crate::config::data_dir;
+use crate::migrations::{self, MigrationInfo};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Mutex, MutexGuard};
 use once_cell::sync::Lazy;
-use crate::error::Result as AppResult;
+use crate::error::{AppError, Result as AppResult};
+use crate::utils::parse_timestamp;
+use crate::output::Render;
+use serde::Serialize;
 use log::info;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Follower {
-    pub did: String,
-    pub handle: String,
-    pub indexed_at: DateTime<Utc>,
-}
-
 static DB_CONNECTION: Lazy<Mutex<Option<Connection>>> = Lazy::new(|| Mutex::new(None));
+static DB_PATH: Lazy<Mutex<Option<PathBuf>>> = Lazy::new(|| Mutex::new(None));
+
+/// Uses the database at `path` instead of the one in the data directory
+///
+/// Call it before any other function here; calling it again with another
+/// path closes the open database so the next call opens the new one.
+pub fn set_db_path(path: PathBuf) {
+    let mut db_path = DB_PATH.lock().unwrap_or_else(|e| e.into_inner());
+    if db_path.as_ref() != Some(&path) {
+        *db_path = Some(path);
+        *DB_CONNECTION.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+}
 
 fn get_db_path() -> Result<PathBuf> {
-    let proj_dirs = ProjectDirs::from("com", "rusty-tools", "bsky")
-        .context("Failed to get project directories")?;
-    
-    let data_dir = proj_dirs.data_dir();
-    fs::create_dir_all(data_dir)?;
-    
-    Ok(data_dir.join("followers.db"))
+    let db_path = DB_PATH.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    match db_path {
+        Some(path) => {
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create {}", parent.display()))?;
+            }
+            Ok(path)
+        }
+        None => Ok(data_dir()?.join("followers.db")),
+    }
+}
+
+/// The shared connection, held until the guard is dropped
+///
+/// Keep it out of `.await` points: the connection is opened once per process
+/// and every caller waits for the previous one to finish with it.
+struct DbConnection(MutexGuard<'static, Option<Connection>>);
+
+impl Deref for DbConnection {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.0.as_ref().expect("get_connection opens the connection before handing it out")
+    }
+}
+
+impl DerefMut for DbConnection {
+    fn deref_mut(&mut self) -> &mut Connection {
+        self.0.as_mut().expect("get_connection opens the connection before handing it out")
+    }
 }
 
-fn get_connection() -> Result<Connection> {
+fn get_connection() -> AppResult<DbConnection> {
     let mut conn_guard = DB_CONNECTION.lock().map_err(|_| anyhow::anyhow!("Failed to lock database connection"))?;
-    
+
     if conn_guard.is_none() {
         let db_path = get_db_path()?;
         let conn = Connection::open(db_path)?;
         init_db(&conn)?;
         *conn_guard = Some(conn);
     }
-    
-    // Create a new connection since rusqlite::Connection doesn't implement Clone
-    let db_path = get_db_path()?;
-    let conn = Connection::open(db_path)?;
-    init_db(&conn)?;
-    Ok(conn)
+
+    Ok(DbConnection(conn_guard))
 }
 
-fn init_db(conn: &Connection) -> Result<()> {
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS followers (
-            did TEXT PRIMARY KEY,
-            handle TEXT NOT NULL,
-            indexed_at DATETIME NOT NULL
-        )",
-        [],
-    )?;
+/// Brings the database to the latest schema version
+fn init_db(conn: &Connection) -> AppResult<()> {
+    migrations::migrate(conn)?;
+    Ok(())
+}
 
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS follower_diffs (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            did TEXT NOT NULL,
-            handle TEXT NOT NULL,
-            action TEXT NOT NULL,
-            timestamp DATETIME NOT NULL
-        )",
-        [],
-    )?;
+/// Schema version of the database and the migrations it still needs
+#[derive(Debug, Serialize)]
+pub struct SchemaStatus {
+    pub path: PathBuf,
+    pub version: u32,
+    pub latest: u32,
+    pub pending: Vec<MigrationInfo>,
+    /// Snapshots from before multi-account support that no account has claimed
+    pub unowned_snapshots: i64,
+    /// Recorded changes from before multi-account support that no account has claimed
+    pub unowned_diffs: i64,
+}
 
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS config (
-            key TEXT PRIMARY KEY,
-            value TEXT NOT NULL
-        )",
-        [],
-    )?;
+impl Render for SchemaStatus {
+    fn text(&self) -> String {
+        let mut lines = vec![
+            format!("Database: {}", self.path.display()),
+            format!("Schema version: {} of {}", self.version, self.latest),
+        ];
+        if self.pending.is_empty() {
+            lines.push("Up to date".to_string());
+        } else {
+            lines.push("Pending migrations:".to_string());
+            lines.extend(self.pending.iter().map(|m| format!("  {}: {}", m.version, m.description)));
+        }
+        if self.unowned_snapshots > 0 || self.unowned_diffs > 0 {
+            lines.push(format!(
+                "{} snapshots and {} recorded changes from before multi-account support have no owner",
+                self.unowned_snapshots, self.unowned_diffs
+            ));
+        }
+        lines.join("\n")
+    }
+}
 
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS handles (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            handle TEXT NOT NULL
-        )",
+/// Reports the schema version without migrating the database
+pub fn schema_status() -> AppResult<SchemaStatus> {
+    let path = get_db_path()?;
+    let (version, pending, unowned) = if path.exists() {
+        let conn = Connection::open_with_flags(&path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let pending: Vec<MigrationInfo> = migrations::pending(&conn)?.iter().map(MigrationInfo::from).collect();
+        // Ownership can only be read once the snapshots table exists
+        let has_snapshots: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'snapshots')",
+            [],
+            |row| row.get(0),
+        )?;
+        let unowned = if has_snapshots { count_unowned_rows(&conn)? } else { (0, 0) };
+        (migrations::schema_version(&conn)?, pending, unowned)
+    } else {
+        (0, migrations::MIGRATIONS.iter().map(MigrationInfo::from).collect(), (0, 0))
+    };
+    Ok(SchemaStatus {
+        path,
+        version,
+        latest: migrations::latest_version(),
+        pending,
+        unowned_snapshots: unowned.0,
+        unowned_diffs: unowned.1,
+    })
+}
+
+/// Outcome of the `db migrate` command
+#[derive(Debug, Serialize)]
+pub struct MigrationReport {
+    pub path: PathBuf,
+    pub from: u32,
+    pub to: u32,
+    pub applied: Vec<MigrationInfo>,
+}
+
+impl Render for MigrationReport {
+    fn text(&self) -> String {
+        if self.applied.is_empty() {
+            return format!("{} is already at schema version {}", self.path.display(), self.to);
+        }
+        let mut lines = vec![format!(
+            "Migrated {} from schema version {} to {}",
+            self.path.display(),
+            self.from,
+            self.to
+        )];
+        lines.extend(self.applied.iter().map(|m| format!("  {}: {}", m.version, m.description)));
+        lines.join("\n")
+    }
+}
+
+/// Applies pending migrations, reporting which ones ran
+///
+/// Other commands migrate the database when they first open it; this makes
+/// the upgrade explicit.
+pub fn migrate_schema() -> AppResult<MigrationReport> {
+    let path = get_db_path()?;
+    let conn = Connection::open(&path)?;
+    let from = migrations::schema_version(&conn)?;
+    let applied = migrations::migrate(&conn)?.into_iter().map(MigrationInfo::from).collect();
+    Ok(MigrationReport {
+        path,
+        from,
+        to: migrations::schema_version(&conn)?,
+        applied,
+    })
+}
+
+/// Rows written before multi-account support, as `(snapshots, diffs)`
+fn count_unowned_rows(conn: &Connection) -> rusqlite::Result<(i64, i64)> {
+    conn.query_row(
+        "SELECT (SELECT COUNT(*) FROM snapshots WHERE owner_did = ''),
+                (SELECT COUNT(*) FROM follower_diffs WHERE owner_did = '')",
         [],
-    )?;
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+}
 
+/// Hands rows written before multi-account support to `owner_did` if they are theirs
+///
+/// They belong to the account imported from the single-account `session.json`,
+/// or the one the legacy `handles` table names. Anyone else leaves them
+/// unowned, and `db status` reports them.
+fn adopt_unowned_rows(conn: &Connection, owner_did: &str) -> AppResult<()> {
+    if count_unowned_rows(conn)? == (0, 0) {
+        return Ok(());
+    }
+    let legacy_owner = crate::auth::legacy_owner(saved_handle(conn)?.as_deref())?;
+    adopt_rows_of(conn, owner_did, legacy_owner.as_deref())?;
     Ok(())
 }
 
-pub fn save_handle(handle: &str) -> AppResult<()> {
-    let conn = get_connection()?;
-    conn.execute(
-        "INSERT INTO handles (handle) VALUES (?1)",
-        params![handle],
-    )?;
+fn adopt_rows_of(conn: &Connection, owner_did: &str, legacy_owner: Option<&str>) -> Result<()> {
+    if legacy_owner == Some(owner_did) {
+        conn.execute("UPDATE snapshots SET owner_did = ?1 WHERE owner_did = ''", params![owner_did])?;
+        conn.execute("UPDATE follower_diffs SET owner_did = ?1 WHERE owner_did = ''", params![owner_did])?;
+    }
     Ok(())
 }
 
+fn saved_handle(conn: &Connection) -> rusqlite::Result<Option<String>> {
+    conn.query_row("SELECT handle FROM handles ORDER BY id DESC LIMIT 1", [], |row| row.get(0))
+        .optional()
+}
+
+/// Handle saved by versions that kept it in the database instead of `config.toml`
 pub fn get_saved_handle() -> AppResult<Option<String>> {
     let conn = get_connection()?;
-    let mut stmt = conn.prepare("SELECT handle FROM handles ORDER BY id DESC LIMIT 1")?;
-    let handle = stmt.query_row([], |row| row.get(0))
-        .optional()?;
-    Ok(handle)
+    Ok(saved_handle(&conn)?)
 }
 
 // Convert DateTime to ISO8601 string for SQLite storage
@@ -111,112 +237,991 @@ fn datetime_to_sqlite(dt: &DateTime<Utc>) -> String {
     dt.to_rfc3339()
 }
 
-pub async fn save_followers() -> AppResult<()> {
-    let session = get_session()?;
+/// Outcome of the `save-followers` and `save-following` commands
+#[derive(Debug, Serialize)]
+pub struct SavedList {
+    pub handle: String,
+    pub kind: GraphKind,
+    pub count: usize,
+}
 
-    println!("Fetching followers for @{}...", session.handle);
+impl Render for SavedList {
+    fn text(&self) -> String {
+        format!("Saved {} {} to database", self.count, self.kind.as_str())
+    }
+}
+
+/// Fetches our `kind` list and stores it as a snapshot
+async fn save_list(client: &XrpcClient, kind: GraphKind) -> AppResult<SavedList> {
+    let session = client.session()?;
+
+    info!("Fetching {} of @{}", kind.as_str(), session.handle);
 
-    let client = Client::new();
-    let response = client
-        .get(format!("{}/com.atproto.repo.getFollowers", BLUESKY_API_URL))
-        .header("Authorization", format!("Bearer {}", session.access_jwt))
-        .query(&[("actor", &session.handle)])
-        .send()
-        .await?;
+    let accounts = match kind {
+        GraphKind::Followers => get_followers(client, PageOptions::default()).await?,
+        GraphKind::Follows => get_following(client, PageOptions::default()).await?,
+    };
 
-    let followers: Vec<Follower> = response.json().await?;
-    let followers_count = followers.len();
-    
     let mut conn = get_connection()?;
     let tx = conn.transaction()?;
+    adopt_unowned_rows(&tx, &session.did)?;
+    store_snapshot(&tx, kind, &session.did, &accounts)?;
+    tx.commit()?;
+    Ok(SavedList {
+        handle: session.handle,
+        kind,
+        count: accounts.len(),
+    })
+}
 
-    // Clear existing followers
-    tx.execute("DELETE FROM followers", [])?;
-
-    // Insert new followers
-    for follower in followers {
-        tx.execute(
-            "INSERT INTO followers (did, handle, indexed_at) VALUES (?1, ?2, ?3)",
-            params![
-                follower.did,
-                follower.handle,
-                datetime_to_sqlite(&follower.indexed_at)
-            ],
-        )?;
+pub async fn save_followers(client: &XrpcClient) -> AppResult<SavedList> {
+    save_list(client, GraphKind::Followers).await
+}
+
+/// Stores the accounts we follow, like [`save_followers`] does for followers
+pub async fn save_following(client: &XrpcClient) -> AppResult<SavedList> {
+    save_list(client, GraphKind::Follows).await
+}
+
+/// A DID and the handle it was seen with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Account {
+    pub did: String,
+    pub handle: String,
+}
+
+impl From<&Follower> for Account {
+    fn from(follower: &Follower) -> Self {
+        Self {
+            did: follower.did.clone(),
+            handle: follower.handle.clone(),
+        }
     }
+}
 
-    tx.commit()?;
-    println!("Saved {} followers to database", followers_count);
-    Ok(())
+/// Accounts that appeared in, vanished from or were renamed in a follower list
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct FollowerDiff {
+    pub new_followers: Vec<Account>,
+    pub unfollowers: Vec<Account>,
+    pub handle_changes: Vec<HandleChange>,
 }
 
-pub async fn compare_followers() -> AppResult<()> {
-    let session = get_session()?;
+impl FollowerDiff {
+    /// Text lines for the accounts that came and went
+    fn membership_lines(&self) -> Vec<String> {
+        self.new_followers
+            .iter()
+            .map(|account| format!("🆕 New follower: @{}", account.handle))
+            .chain(
+                self.unfollowers
+                    .iter()
+                    .map(|account| format!("❌ Unfollower: @{}", account.handle)),
+            )
+            .collect()
+    }
 
-    println!("Fetching current followers for @{}...", session.handle);
+    fn membership_records(&self) -> Vec<serde_json::Value> {
+        let mut records = account_records(&self.new_followers, "follow");
+        records.extend(account_records(&self.unfollowers, "unfollow"));
+        records
+    }
+}
+
+impl Render for FollowerDiff {
+    fn text(&self) -> String {
+        if *self == FollowerDiff::default() {
+            return "No follower changes".to_string();
+        }
+
+        let mut lines = self.membership_lines();
+        lines.extend(self.handle_changes.iter().map(HandleChange::line));
+        lines.join("\n")
+    }
+
+    fn records(&self) -> AppResult<Vec<serde_json::Value>> {
+        let mut records = self.membership_records();
+        records.extend(self.handle_changes.iter().map(HandleChange::record));
+        Ok(records)
+    }
+}
+
+/// One `{action, did, handle}` record per account
+fn account_records(accounts: &[Account], action: &str) -> Vec<serde_json::Value> {
+    accounts
+        .iter()
+        .map(|account| {
+            serde_json::json!({
+                "action": action,
+                "did": account.did,
+                "handle": account.handle,
+            })
+        })
+        .collect()
+}
+
+/// Compares two follower lists by DID, keeping the order of each list
+///
+/// An account in both lists under a different handle counts as a handle change.
+pub fn diff_followers(previous: &[Account], current: &[Account]) -> FollowerDiff {
+    let previous_dids: HashSet<&str> = previous.iter().map(|a| a.did.as_str()).collect();
+    let current_dids: HashSet<&str> = current.iter().map(|a| a.did.as_str()).collect();
+
+    FollowerDiff {
+        new_followers: current
+            .iter()
+            .filter(|a| !previous_dids.contains(a.did.as_str()))
+            .cloned()
+            .collect(),
+        unfollowers: previous
+            .iter()
+            .filter(|a| !current_dids.contains(a.did.as_str()))
+            .cloned()
+            .collect(),
+        handle_changes: handle_changes(previous, current),
+    }
+}
+
+/// Compares the live follower list with the latest snapshot, records the
+/// changes and stores the list as the new latest snapshot
+///
+/// Without an earlier snapshot the list only becomes the baseline, and the
+/// returned diff is empty.
+pub async fn compare_followers(client: &XrpcClient) -> AppResult<FollowerDiff> {
+    let session = client.session()?;
+
+    info!("Fetching current followers for @{}", session.handle);
 
-    let client = Client::new();
-    let response = client
-        .get(format!("{}/com.atproto.repo.getFollowers", BLUESKY_API_URL))
-        .header("Authorization", format!("Bearer {}", session.access_jwt))
-        .query(&[("actor", &session.handle)])
-        .send()
-        .await?;
+    let followers = get_followers(client, PageOptions::default()).await?;
+    let current_followers: Vec<Account> = followers.iter().map(Account::from).collect();
 
-    let current_followers: Vec<Follower> = response.json().await?;
-    
     let mut conn = get_connection()?;
     let tx = conn.transaction()?;
+    adopt_unowned_rows(&tx, &session.did)?;
+
+    if !has_snapshot(&tx, GraphKind::Followers, &session.did)? {
+        info!("No earlier follower snapshot; saving this one as the baseline");
+        store_snapshot(&tx, GraphKind::Followers, &session.did, &followers)?;
+        tx.commit()?;
+        return Ok(FollowerDiff::default());
+    }
 
-    // Get previous followers
-    let mut stmt = tx.prepare("SELECT did, handle FROM followers")?;
-    let previous_followers: Vec<(String, String)> = stmt
-        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+    let previous_followers = load_accounts(&tx, GraphKind::Followers, &session.did)?;
+    let diff = diff_followers(&previous_followers, &current_followers);
+
+    for account in &diff.new_followers {
+        record_diff(&tx, &session.did, account, "follow")?;
+    }
+
+    for account in &diff.unfollowers {
+        record_diff(&tx, &session.did, account, "unfollow")?;
+    }
+
+    for change in &diff.handle_changes {
+        record_handle_change(&tx, &session.did, change)?;
+    }
+
+    // Later runs compare against this list, so changes are only recorded once
+    store_snapshot(&tx, GraphKind::Followers, &session.did, &followers)?;
+    tx.commit()?;
+    Ok(diff)
+}
+
+/// Members of the latest `kind` snapshot of `owner_did`
+///
+/// The `followers` and `follows` views always show the latest snapshot.
+fn load_accounts(conn: &Connection, kind: GraphKind, owner_did: &str) -> AppResult<Vec<Account>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT did, handle FROM {} WHERE owner_did = ?1",
+        kind.as_str()
+    ))?;
+    let accounts = stmt
+        .query_map(params![owner_did], |row| Ok(Account { did: row.get(0)?, handle: row.get(1)? }))?
         .collect::<Result<Vec<_>, _>>()?;
+    Ok(accounts)
+}
 
-    let current_dids: std::collections::HashSet<_> = current_followers
-        .iter()
-        .map(|f| &f.did)
+/// The `kind` list stored for `owner_did` by the last snapshot, sorted by handle
+pub fn stored_accounts(owner_did: &str, kind: GraphKind) -> AppResult<Vec<Account>> {
+    let conn = get_connection()?;
+    let mut accounts = load_accounts(&conn, kind, owner_did)?;
+    accounts.sort_by(|a, b| a.handle.cmp(&b.handle));
+    Ok(accounts)
+}
+
+/// A change recorded in `follower_diffs`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DiffEvent {
+    pub did: String,
+    pub handle: String,
+    /// `follow`, `unfollow`, `start_following`, `stop_following` or `handle_change`
+    pub action: String,
+    /// Handle the account had before a `handle_change`
+    pub old_handle: Option<String>,
+    pub timestamp: String,
+}
+
+/// The latest `limit` changes recorded for `owner_did`, newest first
+pub fn recent_diffs(owner_did: &str, limit: usize) -> AppResult<Vec<DiffEvent>> {
+    let conn = get_connection()?;
+    let mut stmt = conn.prepare(
+        "SELECT did, handle, action, old_handle, timestamp FROM follower_diffs
+         WHERE owner_did = ?1 ORDER BY id DESC LIMIT ?2",
+    )?;
+    let events = stmt
+        .query_map(params![owner_did, limit as i64], |row| {
+            Ok(DiffEvent {
+                did: row.get(0)?,
+                handle: row.get(1)?,
+                action: row.get(2)?,
+                old_handle: row.get(3)?,
+                timestamp: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(events)
+}
+
+/// Whether a `kind` snapshot of `owner_did` was ever stored, even an empty one
+fn has_snapshot(conn: &Connection, kind: GraphKind, owner_did: &str) -> AppResult<bool> {
+    let exists = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM snapshots WHERE owner_did = ?1 AND kind = ?2)",
+        params![owner_did, kind.as_str()],
+        |row| row.get(0),
+    )?;
+    Ok(exists)
+}
+
+/// Stores `accounts` as a new `kind` snapshot of `owner_did`
+///
+/// Nothing is stored when the list (DIDs and handles) is the same as in the
+/// latest snapshot, which then still describes it; returns the new snapshot's id.
+fn store_snapshot(
+    conn: &Connection,
+    kind: GraphKind,
+    owner_did: &str,
+    accounts: &[Follower],
+) -> AppResult<Option<i64>> {
+    let previous: HashSet<(String, String)> = load_accounts(conn, kind, owner_did)?
+        .into_iter()
+        .map(|a| (a.did, a.handle))
         .collect();
+    let unchanged = previous.len() == accounts.len()
+        && accounts
+            .iter()
+            .all(|a| previous.contains(&(a.did.clone(), a.handle.clone())));
+    if unchanged && has_snapshot(conn, kind, owner_did)? {
+        return Ok(None);
+    }
+
+    let taken_at = Utc::now();
+    conn.execute(
+        "INSERT INTO snapshots (owner_did, kind, taken_at, count) VALUES (?1, ?2, ?3, ?4)",
+        params![owner_did, kind.as_str(), datetime_to_sqlite(&taken_at), accounts.len() as i64],
+    )?;
+    let snapshot_id = conn.last_insert_rowid();
+
+    let mut stmt = conn.prepare(
+        "INSERT OR REPLACE INTO snapshot_members (snapshot_id, did, handle, indexed_at) VALUES (?1, ?2, ?3, ?4)",
+    )?;
+    for account in accounts {
+        stmt.execute(params![
+            snapshot_id,
+            account.did,
+            account.handle,
+            datetime_to_sqlite(&account.indexed_at.unwrap_or(taken_at))
+        ])?;
+    }
+    Ok(Some(snapshot_id))
+}
+
+/// A stored copy of one list as it was at `taken_at`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Snapshot {
+    pub id: i64,
+    pub kind: GraphKind,
+    pub taken_at: DateTime<Utc>,
+    pub count: i64,
+}
+
+impl Snapshot {
+    fn summary(&self) -> String {
+        format!(
+            "#{}  {}  {}  {} accounts",
+            self.id,
+            self.taken_at.format("%Y-%m-%d %H:%M:%S"),
+            self.kind.as_str(),
+            self.count
+        )
+    }
+}
+
+/// Snapshots listed by the `snapshots list` command, oldest first
+#[derive(Debug, Serialize)]
+pub struct SnapshotList {
+    pub snapshots: Vec<Snapshot>,
+}
+
+impl Render for SnapshotList {
+    fn text(&self) -> String {
+        if self.snapshots.is_empty() {
+            return "No snapshots stored".to_string();
+        }
+        self.snapshots.iter().map(Snapshot::summary).collect::<Vec<_>>().join("\n")
+    }
+
+    fn records(&self) -> AppResult<Vec<serde_json::Value>> {
+        self.snapshots
+            .iter()
+            .map(|snapshot| Ok(serde_json::to_value(snapshot)?))
+            .collect()
+    }
+}
+
+/// Names a stored snapshot by id, or by the time it has to describe
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotRef {
+    Id(i64),
+    /// The latest snapshot taken at or before this time
+    At(DateTime<Utc>),
+    /// The most recent snapshot
+    Latest,
+}
+
+impl std::str::FromStr for SnapshotRef {
+    type Err = String;
+
+    /// Accepts a snapshot id, an RFC 3339 time, a `YYYY-MM-DD` date (meaning
+    /// the end of that day, UTC) or `latest`
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let s = s.trim().trim_start_matches('#');
+        if s.eq_ignore_ascii_case("latest") {
+            return Ok(SnapshotRef::Latest);
+        }
+        if let Ok(id) = s.parse() {
+            return Ok(SnapshotRef::Id(id));
+        }
+        if let Ok(at) = DateTime::parse_from_rfc3339(s) {
+            return Ok(SnapshotRef::At(at.with_timezone(&Utc)));
+        }
+        if let Ok(date) = chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            let end_of_day = date.and_hms_opt(23, 59, 59).expect("23:59:59 is a valid time");
+            return Ok(SnapshotRef::At(end_of_day.and_utc()));
+        }
+        Err(format!(
+            "expected a snapshot id, a date (YYYY-MM-DD), an RFC 3339 time or latest, got {}",
+            s
+        ))
+    }
+}
+
+fn load_snapshots(conn: &Connection, owner_did: &str, kind: Option<GraphKind>) -> AppResult<Vec<Snapshot>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, kind, taken_at, count FROM snapshots
+         WHERE owner_did = ?1 AND (?2 IS NULL OR kind = ?2) ORDER BY id",
+    )?;
+    let rows = stmt
+        .query_map(params![owner_did, kind.map(GraphKind::as_str)], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get(3)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    rows.into_iter()
+        .map(|(id, kind, taken_at, count)| {
+            Ok(Snapshot {
+                id,
+                kind: match kind.as_str() {
+                    "follows" => GraphKind::Follows,
+                    _ => GraphKind::Followers,
+                },
+                taken_at: parse_timestamp(&taken_at)?,
+                count,
+            })
+        })
+        .collect()
+}
+
+/// Snapshots stored for `owner_did`, of one list or both
+pub fn list_snapshots(owner_did: &str, kind: Option<GraphKind>) -> AppResult<SnapshotList> {
+    let conn = get_connection()?;
+    adopt_unowned_rows(&conn, owner_did)?;
+    Ok(SnapshotList {
+        snapshots: load_snapshots(&conn, owner_did, kind)?,
+    })
+}
+
+/// Looks up the `kind` snapshot of `owner_did` that `reference` names
+pub fn find_snapshot(owner_did: &str, kind: GraphKind, reference: SnapshotRef) -> AppResult<Snapshot> {
+    let conn = get_connection()?;
+    adopt_unowned_rows(&conn, owner_did)?;
+    let snapshots = load_snapshots(&conn, owner_did, Some(kind))?;
+
+    let found = match reference {
+        SnapshotRef::Id(id) => snapshots.into_iter().find(|s| s.id == id),
+        SnapshotRef::At(at) => snapshots.into_iter().rev().find(|s| s.taken_at <= at),
+        SnapshotRef::Latest => snapshots.into_iter().last(),
+    };
+    found.ok_or_else(|| {
+        AppError::InvalidInput(match reference {
+            SnapshotRef::Id(id) => format!("No {} snapshot #{}", kind.as_str(), id),
+            SnapshotRef::At(at) => format!("No {} snapshot taken by {}", kind.as_str(), at.to_rfc3339()),
+            SnapshotRef::Latest => format!("No {} snapshots stored", kind.as_str()),
+        })
+    })
+}
+
+/// A snapshot with its members, as shown by `snapshots show`
+#[derive(Debug, Serialize)]
+pub struct SnapshotView {
+    pub snapshot: Snapshot,
+    pub accounts: Vec<Account>,
+}
+
+impl Render for SnapshotView {
+    fn text(&self) -> String {
+        let mut lines = vec![self.snapshot.summary()];
+        lines.extend(self.accounts.iter().map(|a| format!("@{} ({})", a.handle, a.did)));
+        lines.join("\n")
+    }
+
+    fn records(&self) -> AppResult<Vec<serde_json::Value>> {
+        self.accounts
+            .iter()
+            .map(|account| Ok(serde_json::to_value(account)?))
+            .collect()
+    }
+}
+
+fn load_members(conn: &Connection, snapshot_id: i64) -> AppResult<Vec<Account>> {
+    let mut stmt = conn.prepare("SELECT did, handle FROM snapshot_members WHERE snapshot_id = ?1 ORDER BY handle")?;
+    let accounts = stmt
+        .query_map(params![snapshot_id], |row| Ok(Account { did: row.get(0)?, handle: row.get(1)? }))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(accounts)
+}
+
+/// Loads the members of `snapshot`, sorted by handle
+pub fn snapshot_view(snapshot: Snapshot) -> AppResult<SnapshotView> {
+    let conn = get_connection()?;
+    let accounts = load_members(&conn, snapshot.id)?;
+    Ok(SnapshotView { snapshot, accounts })
+}
+
+/// An overlap of our followers and follows, as listed by the relationship commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Relationship {
+    /// They follow us and we follow them
+    Mutuals,
+    /// They follow us, we don't follow back
+    Fans,
+    /// We follow them, they don't follow back
+    NonReciprocal,
+}
+
+/// Accounts in one [`Relationship`] with us
+#[derive(Debug, Serialize)]
+pub struct RelationshipList {
+    pub relationship: Relationship,
+    pub accounts: Vec<Account>,
+}
+
+impl Render for RelationshipList {
+    fn text(&self) -> String {
+        if self.accounts.is_empty() {
+            return match self.relationship {
+                Relationship::Mutuals => "No mutuals",
+                Relationship::Fans => "Everyone who follows you is followed back",
+                Relationship::NonReciprocal => "Everyone you follow follows you back",
+            }
+            .to_string();
+        }
+        self.accounts
+            .iter()
+            .map(|a| format!("@{} ({})", a.handle, a.did))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn records(&self) -> AppResult<Vec<serde_json::Value>> {
+        self.accounts
+            .iter()
+            .map(|account| Ok(serde_json::to_value(account)?))
+            .collect()
+    }
+}
+
+/// Splits `followers` and `follows` by `relationship`, sorted by handle
+pub fn relate(followers: &[Account], follows: &[Account], relationship: Relationship) -> Vec<Account> {
+    let follower_dids: HashSet<&str> = followers.iter().map(|a| a.did.as_str()).collect();
+    let follow_dids: HashSet<&str> = follows.iter().map(|a| a.did.as_str()).collect();
+
+    let mut accounts: Vec<Account> = match relationship {
+        Relationship::Mutuals => follows.iter().filter(|a| follower_dids.contains(a.did.as_str())).cloned().collect(),
+        Relationship::Fans => followers.iter().filter(|a| !follow_dids.contains(a.did.as_str())).cloned().collect(),
+        Relationship::NonReciprocal => follows.iter().filter(|a| !follower_dids.contains(a.did.as_str())).cloned().collect(),
+    };
+    accounts.sort_by(|a, b| a.handle.cmp(&b.handle));
+    accounts
+}
 
-    let previous_dids: std::collections::HashSet<_> = previous_followers
+/// Derives `relationship` from the latest follower and follow snapshots of `owner_did`
+pub fn relationship(owner_did: &str, relationship: Relationship) -> AppResult<RelationshipList> {
+    let conn = get_connection()?;
+    adopt_unowned_rows(&conn, owner_did)?;
+    for (kind, command) in [(GraphKind::Followers, "save-followers"), (GraphKind::Follows, "save-following")] {
+        if load_snapshots(&conn, owner_did, Some(kind))?.is_empty() {
+            return Err(AppError::InvalidInput(format!(
+                "No {} snapshot stored yet: run {} or watch first",
+                kind.as_str(),
+                command
+            )));
+        }
+    }
+
+    let followers = load_accounts(&conn, GraphKind::Followers, owner_did)?;
+    let follows = load_accounts(&conn, GraphKind::Follows, owner_did)?;
+    Ok(RelationshipList {
+        relationship,
+        accounts: relate(&followers, &follows, relationship),
+    })
+}
+
+/// A DID that is in both lists under different handles
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HandleChange {
+    pub did: String,
+    pub old_handle: String,
+    pub new_handle: String,
+}
+
+impl HandleChange {
+    fn line(&self) -> String {
+        format!("✏️ Handle change: @{} is now @{}", self.old_handle, self.new_handle)
+    }
+
+    fn record(&self) -> serde_json::Value {
+        serde_json::json!({
+            "action": "handle_change",
+            "did": self.did,
+            "handle": self.new_handle,
+            "old_handle": self.old_handle,
+        })
+    }
+}
+
+/// Finds the DIDs whose handle differs between `previous` and `current`
+pub fn handle_changes(previous: &[Account], current: &[Account]) -> Vec<HandleChange> {
+    let previous: HashMap<&str, &str> = previous.iter().map(|a| (a.did.as_str(), a.handle.as_str())).collect();
+    current
         .iter()
-        .map(|(did, _)| did)
-        .collect();
+        .filter_map(|account| {
+            let old_handle = previous.get(account.did.as_str())?;
+            (*old_handle != account.handle).then(|| HandleChange {
+                did: account.did.clone(),
+                old_handle: old_handle.to_string(),
+                new_handle: account.handle.clone(),
+            })
+        })
+        .collect()
+}
 
-    // Find new followers
-    for follower in &current_followers {
-        if !previous_dids.contains(&follower.did) {
-            println!("🆕 New follower: @{}", follower.handle);
-            tx.execute(
-                "INSERT INTO follower_diffs (did, handle, action, timestamp) VALUES (?1, ?2, ?3, ?4)",
-                params![
-                    follower.did,
-                    follower.handle,
-                    "follow",
-                    datetime_to_sqlite(&Utc::now())
-                ],
-            )?;
-        }
-    }
-
-    // Find unfollowers
-    for (did, handle) in &previous_followers {
-        if !current_dids.contains(did) {
-            println!("❌ Unfollower: @{}", handle);
-            tx.execute(
-                "INSERT INTO follower_diffs (did, handle, action, timestamp) VALUES (?1, ?2, ?3, ?4)",
-                params![
-                    did,
-                    handle,
-                    "unfollow",
-                    datetime_to_sqlite(&Utc::now())
-                ],
-            )?;
-        }
-    }
-
-    drop(stmt); // Drop the statement before committing
+/// A handle an account was stored under, and when
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HandleSeen {
+    pub handle: String,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// Handles `did` appears under in the snapshots stored for `owner_did`, oldest first
+pub fn handle_history(owner_did: &str, did: &str) -> AppResult<Vec<HandleSeen>> {
+    let conn = get_connection()?;
+    adopt_unowned_rows(&conn, owner_did)?;
+    let mut stmt = conn.prepare(
+        "SELECT m.handle, s.taken_at FROM snapshot_members m
+         JOIN snapshots s ON s.id = m.snapshot_id WHERE m.did = ?1 AND s.owner_did = ?2",
+    )?;
+    let rows = stmt
+        .query_map(params![did, owner_did], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut history: Vec<HandleSeen> = Vec::new();
+    for (handle, taken_at) in rows {
+        let taken_at = parse_timestamp(&taken_at)?;
+        match history.iter_mut().find(|seen| seen.handle == handle) {
+            Some(seen) => {
+                seen.first_seen = seen.first_seen.min(taken_at);
+                seen.last_seen = seen.last_seen.max(taken_at);
+            }
+            None => history.push(HandleSeen {
+                handle,
+                first_seen: taken_at,
+                last_seen: taken_at,
+            }),
+        }
+    }
+    history.sort_by_key(|seen| seen.first_seen);
+    Ok(history)
+}
+
+/// A looked-up profile with the handles it was stored under
+#[derive(Debug, Serialize)]
+pub struct ProfileLookup {
+    #[serde(flatten)]
+    pub profile: Profile,
+    pub handle_history: Vec<HandleSeen>,
+}
+
+impl ProfileLookup {
+    /// Adds the handle history of `profile` stored for `owner_did`
+    pub fn new(owner_did: &str, profile: Profile) -> AppResult<Self> {
+        let handle_history = handle_history(owner_did, &profile.did)?;
+        Ok(Self { profile, handle_history })
+    }
+}
+
+impl Render for ProfileLookup {
+    fn text(&self) -> String {
+        let mut text = self.profile.text();
+        // Only worth showing once the account has been seen under another handle
+        if self.handle_history.iter().any(|seen| seen.handle != self.profile.handle) {
+            text.push_str("\nHandle history:");
+            for seen in &self.handle_history {
+                text.push_str(&format!(
+                    "\n  @{}  {} to {}",
+                    seen.handle,
+                    seen.first_seen.format("%Y-%m-%d"),
+                    seen.last_seen.format("%Y-%m-%d")
+                ));
+            }
+        }
+        text
+    }
+}
+
+/// Differences between two stored snapshots of the same list
+#[derive(Debug, Serialize)]
+pub struct SnapshotDiff {
+    pub from: Snapshot,
+    pub to: Snapshot,
+    pub added: Vec<Account>,
+    pub removed: Vec<Account>,
+    pub handle_changes: Vec<HandleChange>,
+}
+
+impl Render for SnapshotDiff {
+    fn text(&self) -> String {
+        let mut lines = vec![
+            format!("From {}", self.from.summary()),
+            format!("To   {}", self.to.summary()),
+        ];
+        if self.added.is_empty() && self.removed.is_empty() && self.handle_changes.is_empty() {
+            lines.push("No changes".to_string());
+        }
+        lines.extend(self.added.iter().map(|a| format!("+ @{} ({})", a.handle, a.did)));
+        lines.extend(self.removed.iter().map(|a| format!("- @{} ({})", a.handle, a.did)));
+        lines.extend(
+            self.handle_changes
+                .iter()
+                .map(|c| format!("~ @{} is now @{} ({})", c.old_handle, c.new_handle, c.did)),
+        );
+        lines.join("\n")
+    }
+
+    fn records(&self) -> AppResult<Vec<serde_json::Value>> {
+        let rows = |accounts: &[Account], action: &str| {
+            accounts
+                .iter()
+                .map(|account| {
+                    serde_json::json!({
+                        "action": action,
+                        "did": account.did,
+                        "handle": account.handle,
+                        "old_handle": null,
+                    })
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut records = rows(&self.added, "added");
+        records.extend(rows(&self.removed, "removed"));
+        records.extend(self.handle_changes.iter().map(HandleChange::record));
+        Ok(records)
+    }
+}
+
+/// Compares two stored `kind` snapshots of `owner_did` without touching the API
+///
+/// Read-only, so running it again gives the same answer.
+pub fn diff_snapshots(owner_did: &str, kind: GraphKind, from: SnapshotRef, to: SnapshotRef) -> AppResult<SnapshotDiff> {
+    let from = find_snapshot(owner_did, kind, from)?;
+    let to = find_snapshot(owner_did, kind, to)?;
+
+    let conn = get_connection()?;
+    let previous = load_members(&conn, from.id)?;
+    let current = load_members(&conn, to.id)?;
+    let diff = diff_followers(&previous, &current);
+
+    Ok(SnapshotDiff {
+        handle_changes: diff.handle_changes,
+        added: diff.new_followers,
+        removed: diff.unfollowers,
+        from,
+        to,
+    })
+}
+
+/// What changed in both lists since the previous snapshot
+#[derive(Debug, Default, Serialize)]
+pub struct GraphChanges {
+    /// Lists that had no earlier snapshot to compare against
+    pub baseline: Vec<GraphKind>,
+    pub followers: FollowerDiff,
+    pub follows: FollowerDiff,
+}
+
+impl GraphChanges {
+    pub fn is_empty(&self) -> bool {
+        self.followers == FollowerDiff::default() && self.follows == FollowerDiff::default()
+    }
+
+    /// Handle changes seen in either list, once per account
+    pub fn handle_changes(&self) -> Vec<&HandleChange> {
+        let mut seen = HashSet::new();
+        self.followers
+            .handle_changes
+            .iter()
+            .chain(&self.follows.handle_changes)
+            .filter(|change| seen.insert(change.did.as_str()))
+            .collect()
+    }
+}
+
+impl Render for GraphChanges {
+    fn text(&self) -> String {
+        if self.baseline.len() == 2 {
+            return "Took first snapshot".to_string();
+        }
+        let mut lines: Vec<String> = self
+            .baseline
+            .iter()
+            .map(|kind| format!("Took first {} snapshot", kind.as_str()))
+            .collect();
+        if self.is_empty() {
+            lines.push("No changes".to_string());
+            return lines.join("\n");
+        }
+
+        lines.extend(self.followers.membership_lines());
+        lines.extend(self.follows.new_followers.iter().map(|a| format!("➕ Now following: @{}", a.handle)));
+        lines.extend(self.follows.unfollowers.iter().map(|a| format!("➖ No longer following: @{}", a.handle)));
+        lines.extend(self.handle_changes().into_iter().map(HandleChange::line));
+        lines.join("\n")
+    }
+
+    fn records(&self) -> AppResult<Vec<serde_json::Value>> {
+        let mut records = self.followers.membership_records();
+        records.extend(account_records(&self.follows.new_followers, "start_following"));
+        records.extend(account_records(&self.follows.unfollowers, "stop_following"));
+        records.extend(self.handle_changes().into_iter().map(HandleChange::record));
+        Ok(records)
+    }
+}
+
+/// Fetches followers and follows, records what changed and stores them as the new snapshot
+///
+/// The first snapshot of each list only establishes a baseline for it and
+/// records no diffs for that list.
+pub async fn snapshot_graph(client: &XrpcClient) -> AppResult<GraphChanges> {
+    let session = client.session()?;
+    let followers = get_followers(client, PageOptions::default()).await?;
+    let follows = get_following(client, PageOptions::default()).await?;
+
+    let mut conn = get_connection()?;
+    let tx = conn.transaction()?;
+    adopt_unowned_rows(&tx, &session.did)?;
+
+    let mut changes = GraphChanges::default();
+    for (kind, accounts) in [(GraphKind::Followers, &followers), (GraphKind::Follows, &follows)] {
+        if !has_snapshot(&tx, kind, &session.did)? {
+            changes.baseline.push(kind);
+            continue;
+        }
+        let previous = load_accounts(&tx, kind, &session.did)?;
+        let current: Vec<Account> = accounts.iter().map(Account::from).collect();
+        let diff = diff_followers(&previous, &current);
+        match kind {
+            GraphKind::Followers => changes.followers = diff,
+            GraphKind::Follows => changes.follows = diff,
+        }
+    }
+
+    for (accounts, action) in [
+        (&changes.followers.new_followers, "follow"),
+        (&changes.followers.unfollowers, "unfollow"),
+        (&changes.follows.new_followers, "start_following"),
+        (&changes.follows.unfollowers, "stop_following"),
+    ] {
+        for account in accounts {
+            record_diff(&tx, &session.did, account, action)?;
+        }
+    }
+    for change in changes.handle_changes() {
+        record_handle_change(&tx, &session.did, change)?;
+    }
+
+    store_snapshot(&tx, GraphKind::Followers, &session.did, &followers)?;
+    store_snapshot(&tx, GraphKind::Follows, &session.did, &follows)?;
     tx.commit()?;
+    Ok(changes)
+}
+
+fn record_diff(conn: &Connection, owner_did: &str, account: &Account, action: &str) -> AppResult<()> {
+    conn.execute(
+        "INSERT INTO follower_diffs (owner_did, did, handle, action, timestamp) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![owner_did, account.did, account.handle, action, datetime_to_sqlite(&Utc::now())],
+    )?;
+    Ok(())
+}
+
+/// Records a rename as a `handle_change` row keeping both handles
+fn record_handle_change(conn: &Connection, owner_did: &str, change: &HandleChange) -> AppResult<()> {
+    conn.execute(
+        "INSERT INTO follower_diffs (owner_did, did, handle, old_handle, action, timestamp)
+         VALUES (?1, ?2, ?3, ?4, 'handle_change', ?5)",
+        params![owner_did, change.did, change.new_handle, change.old_handle, datetime_to_sqlite(&Utc::now())],
+    )?;
     Ok(())
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accounts(entries: &[(&str, &str)]) -> Vec<Account> {
+        entries
+            .iter()
+            .map(|(did, handle)| Account {
+                did: did.to_string(),
+                handle: handle.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_adopt_unowned_rows_of_single_account_tables() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE followers (did TEXT PRIMARY KEY, handle TEXT NOT NULL, indexed_at DATETIME NOT NULL);
+            CREATE TABLE follower_diffs (id INTEGER PRIMARY KEY AUTOINCREMENT, did TEXT NOT NULL,
+                handle TEXT NOT NULL, action TEXT NOT NULL, timestamp DATETIME NOT NULL);
+            INSERT INTO followers VALUES ('did:plc:a', 'a.test', '2024-01-01T00:00:00Z');",
+        )
+        .unwrap();
+
+        init_db(&conn).unwrap();
+        // Another account touching the database first leaves the rows alone
+        adopt_rows_of(&conn, "did:plc:other", Some("did:plc:me")).unwrap();
+        adopt_rows_of(&conn, "did:plc:other", None).unwrap();
+        assert_eq!(count_unowned_rows(&conn).unwrap(), (1, 0));
+
+        adopt_rows_of(&conn, "did:plc:me", Some("did:plc:me")).unwrap();
+        let owner: String = conn
+            .query_row("SELECT owner_did FROM followers WHERE did = 'did:plc:a'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(owner, "did:plc:me");
+        assert_eq!(count_unowned_rows(&conn).unwrap(), (0, 0));
+    }
+
+    fn follower(did: &str, handle: &str) -> Follower {
+        Follower {
+            did: did.to_string(),
+            handle: handle.to_string(),
+            indexed_at: None,
+            viewer: None,
+        }
+    }
+
+    #[test]
+    fn test_store_snapshot_keeps_history() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        let me = "did:plc:me";
+
+        let first = vec![follower("did:plc:a", "a.test")];
+        assert!(store_snapshot(&conn, GraphKind::Followers, me, &first).unwrap().is_some());
+        assert_eq!(store_snapshot(&conn, GraphKind::Followers, me, &first).unwrap(), None);
+
+        let renamed = vec![follower("did:plc:a", "a2.test")];
+        assert!(store_snapshot(&conn, GraphKind::Followers, me, &renamed).unwrap().is_some());
+        assert!(store_snapshot(&conn, GraphKind::Followers, me, &[]).unwrap().is_some());
+        assert!(store_snapshot(&conn, GraphKind::Follows, me, &[]).unwrap().is_some());
+
+        assert!(load_accounts(&conn, GraphKind::Followers, me).unwrap().is_empty());
+        let snapshots = load_snapshots(&conn, me, Some(GraphKind::Followers)).unwrap();
+        assert_eq!(snapshots.iter().map(|s| s.count).collect::<Vec<_>>(), [1, 1, 0]);
+        assert_eq!(load_snapshots(&conn, me, None).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_parse_snapshot_ref() {
+        assert_eq!("12".parse(), Ok(SnapshotRef::Id(12)));
+        assert_eq!("#12".parse(), Ok(SnapshotRef::Id(12)));
+        let end_of_day = DateTime::parse_from_rfc3339("2024-05-01T23:59:59Z").unwrap().with_timezone(&Utc);
+        assert_eq!("2024-05-01".parse(), Ok(SnapshotRef::At(end_of_day)));
+        let noon = DateTime::parse_from_rfc3339("2024-05-01T12:00:00Z").unwrap().with_timezone(&Utc);
+        assert_eq!("2024-05-01T14:00:00+02:00".parse(), Ok(SnapshotRef::At(noon)));
+        assert_eq!("Latest".parse(), Ok(SnapshotRef::Latest));
+        assert!("yesterday".parse::<SnapshotRef>().is_err());
+    }
+
+    #[test]
+    fn test_handle_changes() {
+        let previous = accounts(&[("did:plc:a", "a.test"), ("did:plc:b", "b.test")]);
+        let current = accounts(&[("did:plc:b", "b2.test"), ("did:plc:c", "c.test"), ("did:plc:a", "a.test")]);
+
+        assert_eq!(
+            handle_changes(&previous, &current),
+            [HandleChange {
+                did: "did:plc:b".to_string(),
+                old_handle: "b.test".to_string(),
+                new_handle: "b2.test".to_string(),
+            }]
+        );
+        assert!(handle_changes(&current, &current).is_empty());
+    }
+
+    #[test]
+    fn test_relate() {
+        let followers = accounts(&[("did:plc:b", "b.test"), ("did:plc:a", "a.test"), ("did:plc:c", "c.test")]);
+        let follows = accounts(&[("did:plc:a", "a.test"), ("did:plc:d", "d.test"), ("did:plc:b", "b.test")]);
+
+        assert_eq!(
+            relate(&followers, &follows, Relationship::Mutuals),
+            accounts(&[("did:plc:a", "a.test"), ("did:plc:b", "b.test")])
+        );
+        assert_eq!(relate(&followers, &follows, Relationship::Fans), accounts(&[("did:plc:c", "c.test")]));
+        assert_eq!(
+            relate(&followers, &follows, Relationship::NonReciprocal),
+            accounts(&[("did:plc:d", "d.test")])
+        );
+    }
+
+    #[test]
+    fn test_diff_followers() {
+        let previous = accounts(&[("did:plc:a", "a.test"), ("did:plc:b", "b.test")]);
+        let current = accounts(&[("did:plc:b", "b.test"), ("did:plc:c", "c.test")]);
+
+        let diff = diff_followers(&previous, &current);
+        assert_eq!(diff.new_followers, accounts(&[("did:plc:c", "c.test")]));
+        assert_eq!(diff.unfollowers, accounts(&[("did:plc:a", "a.test")]));
+
+        assert_eq!(diff_followers(&current, &current), FollowerDiff::default());
+
+        let renamed = accounts(&[("did:plc:b", "b2.test"), ("did:plc:c", "c.test")]);
+        let diff = diff_followers(&current, &renamed);
+        assert!(diff.new_followers.is_empty() && diff.unfollowers.is_empty());
+        assert_eq!(diff.handle_changes.len(), 1);
+    }
+}
@@ -1,3 +1,6 @@
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -11,8 +14,8 @@ pub enum AppError {
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
-    #[error("API error: {0}")]
-    Api(String),
+    #[error("XRPC error: {0}")]
+    Xrpc(Box<XrpcError>),
 
     #[error("Configuration error: {0}")]
     Config(String),
@@ -29,10 +32,232 @@ pub enum AppError {
     #[error("Session error: {0}")]
     Session(String),
 
+    #[error("Identity resolution error: {0}")]
+    Identity(String),
+
+    #[error("Database schema version {version} is newer than this build supports ({supported}); upgrade bsky-rusty-tools")]
+    SchemaTooNew { version: u32, supported: u32 },
+
     #[error("Anyhow error: {0}")]
     Anyhow(#[from] anyhow::Error),
 }
 
+/// The `error` name of a failed XRPC call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum XrpcErrorKind {
+    ExpiredToken,
+    InvalidToken,
+    AuthenticationRequired,
+    AuthFactorTokenRequired,
+    RateLimitExceeded,
+    AccountTakedown,
+    AccountDeactivated,
+    InvalidRequest,
+    /// Any other error name, or an empty string when the body had none
+    Other(String),
+}
+
+impl XrpcErrorKind {
+    fn from_name(name: Option<&str>, status: u16) -> Self {
+        match name {
+            Some("ExpiredToken") => XrpcErrorKind::ExpiredToken,
+            Some("InvalidToken") => XrpcErrorKind::InvalidToken,
+            Some("AuthenticationRequired") => XrpcErrorKind::AuthenticationRequired,
+            Some("AuthFactorTokenRequired") => XrpcErrorKind::AuthFactorTokenRequired,
+            Some("RateLimitExceeded") => XrpcErrorKind::RateLimitExceeded,
+            Some("AccountTakedown") => XrpcErrorKind::AccountTakedown,
+            Some("AccountDeactivated") => XrpcErrorKind::AccountDeactivated,
+            Some("InvalidRequest") => XrpcErrorKind::InvalidRequest,
+            Some(other) => XrpcErrorKind::Other(other.to_string()),
+            None if status == 429 => XrpcErrorKind::RateLimitExceeded,
+            None if status == 401 => XrpcErrorKind::AuthenticationRequired,
+            None => XrpcErrorKind::Other(String::new()),
+        }
+    }
+}
+
+impl fmt::Display for XrpcErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            XrpcErrorKind::Other(name) if name.is_empty() => write!(f, "UnknownError"),
+            XrpcErrorKind::Other(name) => write!(f, "{}", name),
+            kind => write!(f, "{:?}", kind),
+        }
+    }
+}
+
+/// Values of the `ratelimit-*` response headers
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RateLimitInfo {
+    pub limit: Option<u64>,
+    pub remaining: Option<u64>,
+    /// Unix timestamp (seconds) at which the window resets
+    pub reset: Option<i64>,
+    pub policy: Option<String>,
+}
+
+impl RateLimitInfo {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::trim)
+        };
+
+        Self {
+            limit: header("ratelimit-limit").and_then(|v| v.parse().ok()),
+            remaining: header("ratelimit-remaining").and_then(|v| v.parse().ok()),
+            reset: header("ratelimit-reset").and_then(|v| v.parse().ok()),
+            policy: header("ratelimit-policy").map(str::to_string),
+        }
+    }
+}
+
+/// A failed XRPC call decoded from its `{error, message}` body
+#[derive(Debug, Clone)]
+pub struct XrpcError {
+    pub kind: XrpcErrorKind,
+    pub status: u16,
+    pub message: Option<String>,
+    pub rate_limit: RateLimitInfo,
+}
+
+#[derive(Debug, Deserialize)]
+struct XrpcErrorBody {
+    error: Option<String>,
+    message: Option<String>,
+}
+
+impl XrpcError {
+    /// Decodes an error response; bodies that aren't XRPC JSON become the message
+    pub fn from_response(status: u16, headers: &HeaderMap, body: &str) -> Self {
+        let (name, message) = match serde_json::from_str::<XrpcErrorBody>(body) {
+            Ok(parsed) => (parsed.error, parsed.message),
+            Err(_) => (None, Some(body.trim().to_string()).filter(|m| !m.is_empty())),
+        };
+
+        Self {
+            kind: XrpcErrorKind::from_name(name.as_deref(), status),
+            status,
+            message,
+            rate_limit: RateLimitInfo::from_headers(headers),
+        }
+    }
+}
+
+impl fmt::Display for XrpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (HTTP {})", self.kind, self.status)?;
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<XrpcError> for AppError {
+    fn from(err: XrpcError) -> Self {
+        AppError::Xrpc(Box::new(err))
+    }
+}
+
+impl AppError {
+    /// Returns the XRPC error kind if this error came from a failed XRPC call
+    pub fn xrpc_kind(&self) -> Option<&XrpcErrorKind> {
+        match self {
+            AppError::Xrpc(e) => Some(&e.kind),
+            _ => None,
+        }
+    }
+}
+
+/// Broad kind of failure, each with its own process exit code
+///
+/// | Code | Category        | Meaning                                          |
+/// |------|-----------------|--------------------------------------------------|
+/// | 0    |                 | Success                                          |
+/// | 1    | `internal`      | Unexpected failure (I/O, bugs)                   |
+/// | 2    |                 | Command-line usage error (reported by clap)      |
+/// | 3    | `invalid_input` | A handle, DID or value given to us is malformed  |
+/// | 4    | `auth`          | Not logged in, or the credentials were rejected  |
+/// | 5    | `network`       | The service could not be reached or failed (5xx) |
+/// | 6    | `rate_limited`  | Rate limit still exceeded after retrying         |
+/// | 7    | `database`      | The local SQLite database failed                 |
+/// | 8    | `config`        | The config file is missing or invalid            |
+/// | 9    | `api`           | The service rejected the request                 |
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCategory {
+    Internal,
+    InvalidInput,
+    Auth,
+    Network,
+    RateLimited,
+    Database,
+    Config,
+    Api,
+}
+
+impl ErrorCategory {
+    pub fn exit_code(self) -> u8 {
+        match self {
+            ErrorCategory::Internal => 1,
+            ErrorCategory::InvalidInput => 3,
+            ErrorCategory::Auth => 4,
+            ErrorCategory::Network => 5,
+            ErrorCategory::RateLimited => 6,
+            ErrorCategory::Database => 7,
+            ErrorCategory::Config => 8,
+            ErrorCategory::Api => 9,
+        }
+    }
+}
+
+impl XrpcError {
+    fn category(&self) -> ErrorCategory {
+        match &self.kind {
+            XrpcErrorKind::ExpiredToken
+            | XrpcErrorKind::InvalidToken
+            | XrpcErrorKind::AuthenticationRequired
+            | XrpcErrorKind::AuthFactorTokenRequired
+            | XrpcErrorKind::AccountTakedown
+            | XrpcErrorKind::AccountDeactivated => ErrorCategory::Auth,
+            XrpcErrorKind::RateLimitExceeded => ErrorCategory::RateLimited,
+            _ => match self.status {
+                401 | 403 => ErrorCategory::Auth,
+                429 => ErrorCategory::RateLimited,
+                500.. => ErrorCategory::Network,
+                _ => ErrorCategory::Api,
+            },
+        }
+    }
+}
+
+impl AppError {
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            AppError::Auth(_) | AppError::Session(_) => ErrorCategory::Auth,
+            AppError::Database(_) | AppError::SchemaTooNew { .. } => ErrorCategory::Database,
+            AppError::Io(_) => ErrorCategory::Internal,
+            AppError::Xrpc(e) => e.category(),
+            AppError::Config(_) => ErrorCategory::Config,
+            AppError::Serialization(_) => ErrorCategory::Api,
+            AppError::Request(e) if e.is_decode() => ErrorCategory::Api,
+            AppError::Request(_) => ErrorCategory::Network,
+            AppError::InvalidInput(_) => ErrorCategory::InvalidInput,
+            AppError::Identity(_) => ErrorCategory::Api,
+            // The database layer reports through anyhow
+            AppError::Anyhow(e) if e.downcast_ref::<rusqlite::Error>().is_some() => ErrorCategory::Database,
+            AppError::Anyhow(_) => ErrorCategory::Internal,
+        }
+    }
+
+    pub fn exit_code(&self) -> u8 {
+        self.category().exit_code()
+    }
+}
+
 impl From<AppError> for rusqlite::Error {
     fn from(err: AppError) -> Self {
         match err {
@@ -42,4 +267,65 @@ impl From<AppError> for rusqlite::Error {
     }
 }
 
-pub type Result<T> = std::result::Result<T, AppError>; 
\ No newline at end of file
+pub type Result<T> = std::result::Result<T, AppError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_decode_xrpc_error() {
+        let mut headers = HeaderMap::new();
+        headers.insert("ratelimit-limit", HeaderValue::from_static("3000"));
+        headers.insert("ratelimit-remaining", HeaderValue::from_static("0"));
+        headers.insert("ratelimit-reset", HeaderValue::from_static("1700000000"));
+        headers.insert("ratelimit-policy", HeaderValue::from_static("3000;w=300"));
+
+        let error = XrpcError::from_response(
+            429,
+            &headers,
+            r#"{"error":"RateLimitExceeded","message":"Rate Limit Exceeded"}"#,
+        );
+        assert_eq!(error.kind, XrpcErrorKind::RateLimitExceeded);
+        assert_eq!(error.status, 429);
+        assert_eq!(error.rate_limit.remaining, Some(0));
+        assert_eq!(error.rate_limit.reset, Some(1_700_000_000));
+        assert_eq!(error.rate_limit.policy.as_deref(), Some("3000;w=300"));
+    }
+
+    #[test]
+    fn test_decode_non_json_error() {
+        let error = XrpcError::from_response(502, &HeaderMap::new(), "Bad Gateway");
+        assert_eq!(error.kind, XrpcErrorKind::Other(String::new()));
+        assert_eq!(error.message.as_deref(), Some("Bad Gateway"));
+        assert_eq!(error.to_string(), "UnknownError (HTTP 502): Bad Gateway");
+
+        let error = XrpcError::from_response(400, &HeaderMap::new(), r#"{"error":"ExpiredToken"}"#);
+        assert_eq!(error.kind, XrpcErrorKind::ExpiredToken);
+    }
+
+    #[test]
+    fn test_error_categories() {
+        let rate_limited = XrpcError::from_response(429, &HeaderMap::new(), "");
+        assert_eq!(AppError::from(rate_limited).exit_code(), 6);
+
+        let expired = XrpcError::from_response(
+            400,
+            &HeaderMap::new(),
+            r#"{"error":"ExpiredToken","message":"Token has expired"}"#,
+        );
+        assert_eq!(AppError::from(expired).category(), ErrorCategory::Auth);
+
+        let not_found = XrpcError::from_response(
+            400,
+            &HeaderMap::new(),
+            r#"{"error":"InvalidRequest","message":"Profile not found"}"#,
+        );
+        assert_eq!(AppError::from(not_found).category(), ErrorCategory::Api);
+
+        let db: AppError = anyhow::Error::from(rusqlite::Error::InvalidQuery).into();
+        assert_eq!(db.exit_code(), 7);
+        assert_eq!(AppError::InvalidInput("bad handle".to_string()).exit_code(), 3);
+    }
+}
@@ -0,0 +1,366 @@
+use crate::client::{Endpoints, USER_AGENT};
+use crate::error::{AppError, Result};
+use crate::output::Render;
+use hickory_resolver::TokioAsyncResolver;
+use log::debug;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+/// PLC directory used when none is configured
+pub const DEFAULT_PLC_DIRECTORY: &str = "https://plc.directory";
+
+/// A DID document as published by the PLC directory or a did:web host
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DidDocument {
+    pub id: String,
+    #[serde(default)]
+    pub also_known_as: Vec<String>,
+    #[serde(default)]
+    pub verification_method: Vec<VerificationMethod>,
+    #[serde(default)]
+    pub service: Vec<ServiceEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerificationMethod {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub controller: String,
+    pub public_key_multibase: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceEntry {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub service_endpoint: String,
+}
+
+impl DidDocument {
+    /// Endpoint of the account's PDS (`#atproto_pds` service)
+    pub fn pds_endpoint(&self) -> Option<&str> {
+        self.service
+            .iter()
+            .find(|service| service.id.ends_with("#atproto_pds"))
+            .map(|service| service.service_endpoint.as_str())
+    }
+
+    /// Multibase-encoded repo signing key (`#atproto` verification method)
+    pub fn signing_key(&self) -> Option<&str> {
+        self.verification_method
+            .iter()
+            .find(|method| method.id.ends_with("#atproto"))
+            .and_then(|method| method.public_key_multibase.as_deref())
+    }
+
+    /// Handle claimed by the document through an `at://` alias
+    pub fn handle(&self) -> Option<&str> {
+        self.also_known_as
+            .iter()
+            .find_map(|alias| alias.strip_prefix("at://"))
+    }
+}
+
+/// Resolves handles to DIDs and DIDs to their documents
+#[derive(Debug, Clone)]
+pub struct IdentityResolver {
+    http: Client,
+    plc_directory: String,
+    use_dns: bool,
+    scheme: &'static str,
+}
+
+impl IdentityResolver {
+    pub fn new() -> Result<Self> {
+        let http = Client::builder().user_agent(USER_AGENT).build()?;
+        Ok(Self {
+            http,
+            plc_directory: DEFAULT_PLC_DIRECTORY.to_string(),
+            use_dns: true,
+            scheme: "https",
+        })
+    }
+
+    /// Creates a resolver using the PLC directory chosen in `endpoints`
+    pub fn for_endpoints(endpoints: &Endpoints) -> Result<Self> {
+        let resolver = Self::new()?;
+        Ok(match &endpoints.plc_directory {
+            Some(plc_directory) => resolver.with_plc_directory(plc_directory),
+            None => resolver,
+        })
+    }
+
+    /// Resolves `did:plc` identifiers through another PLC directory
+    pub fn with_plc_directory(mut self, plc_directory: &str) -> Self {
+        self.plc_directory = plc_directory.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Skips the `_atproto` TXT lookup and only uses `/.well-known/atproto-did`
+    pub fn without_dns(mut self) -> Self {
+        self.use_dns = false;
+        self
+    }
+
+    /// Fetches well-known documents over plain HTTP, for local test servers
+    pub fn with_insecure_http(mut self) -> Self {
+        self.scheme = "http";
+        self
+    }
+
+    /// Resolves a handle or DID to its DID document
+    pub async fn resolve(&self, actor: &str) -> Result<DidDocument> {
+        let actor = normalize_actor(actor)?;
+        let did = if actor.starts_with("did:") {
+            actor
+        } else {
+            self.resolve_handle(&actor).await?
+        };
+        self.resolve_did(&did).await
+    }
+
+    /// Resolves a handle through DNS first, then through HTTPS
+    pub async fn resolve_handle(&self, handle: &str) -> Result<String> {
+        if self.use_dns {
+            match self.resolve_handle_dns(handle).await {
+                Ok(Some(did)) => return Ok(did),
+                Ok(None) => debug!("No _atproto TXT record for {}", handle),
+                Err(e) => debug!("DNS lookup for {} failed: {}", handle, e),
+            }
+        }
+        self.resolve_handle_http(handle).await
+    }
+
+    async fn resolve_handle_dns(&self, handle: &str) -> Result<Option<String>> {
+        let resolver = TokioAsyncResolver::tokio_from_system_conf()
+            .map_err(|e| AppError::Identity(format!("DNS resolver unavailable: {}", e)))?;
+        let lookup = resolver
+            .txt_lookup(format!("_atproto.{}.", handle))
+            .await
+            .map_err(|e| AppError::Identity(e.to_string()))?;
+
+        let dids: Vec<String> = lookup
+            .iter()
+            .map(|txt| txt.to_string())
+            .filter_map(|value| value.strip_prefix("did=").map(str::to_string))
+            .collect();
+
+        // Multiple conflicting records mean the handle is misconfigured
+        match dids.as_slice() {
+            [] => Ok(None),
+            [did] => Ok(Some(did.clone())),
+            _ => Err(AppError::Identity(format!(
+                "Multiple _atproto records for {}",
+                handle
+            ))),
+        }
+    }
+
+    async fn resolve_handle_http(&self, handle: &str) -> Result<String> {
+        let url = format!("{}://{}/.well-known/atproto-did", self.scheme, handle);
+        let response = self.http.get(&url).send().await?;
+        if !response.status().is_success() {
+            return Err(AppError::Identity(format!(
+                "Could not resolve handle {} ({})",
+                handle,
+                response.status()
+            )));
+        }
+
+        let did = response.text().await?.trim().to_string();
+        if !did.starts_with("did:") {
+            return Err(AppError::Identity(format!(
+                "{} did not return a DID",
+                url
+            )));
+        }
+        Ok(did)
+    }
+
+    /// Fetches the DID document for a `did:plc` or `did:web` identifier
+    pub async fn resolve_did(&self, did: &str) -> Result<DidDocument> {
+        let url = if did.starts_with("did:plc:") {
+            format!("{}/{}", self.plc_directory, did)
+        } else if let Some(host) = did.strip_prefix("did:web:") {
+            // did:web encodes a port as %3A; paths are not allowed in atproto
+            let host = host.replace("%3A", ":");
+            format!("{}://{}/.well-known/did.json", self.scheme, host)
+        } else {
+            return Err(AppError::InvalidInput(format!(
+                "Unsupported DID method: {}",
+                did
+            )));
+        };
+
+        let response = self.http.get(&url).send().await?;
+        if !response.status().is_success() {
+            return Err(AppError::Identity(format!(
+                "Could not resolve {} ({})",
+                did,
+                response.status()
+            )));
+        }
+
+        let document: DidDocument = response.json().await?;
+        if document.id != did {
+            return Err(AppError::Identity(format!(
+                "DID document for {} describes {}",
+                did, document.id
+            )));
+        }
+        Ok(document)
+    }
+}
+
+/// The parts of a DID document shown by the `resolve` command
+#[derive(Debug, Serialize)]
+pub struct Identity {
+    pub did: String,
+    pub handle: Option<String>,
+    pub pds: Option<String>,
+    pub signing_key: Option<String>,
+}
+
+impl From<&DidDocument> for Identity {
+    fn from(document: &DidDocument) -> Self {
+        Self {
+            did: document.id.clone(),
+            handle: document.handle().map(str::to_string),
+            pds: document.pds_endpoint().map(str::to_string),
+            signing_key: document.signing_key().map(str::to_string),
+        }
+    }
+}
+
+impl Render for Identity {
+    fn text(&self) -> String {
+        let mut lines = vec![format!("DID: {}", self.did)];
+        if let Some(handle) = &self.handle {
+            lines.push(format!("Handle: {}", handle));
+        }
+        if let Some(pds) = &self.pds {
+            lines.push(format!("PDS: {}", pds));
+        }
+        if let Some(key) = &self.signing_key {
+            lines.push(format!("Signing key: {}", key));
+        }
+        lines.join("\n")
+    }
+}
+
+/// Strips a leading `@`, lowercases handles and rejects malformed actors
+pub fn normalize_actor(actor: &str) -> Result<String> {
+    let actor = actor.trim().trim_start_matches('@');
+
+    if actor.starts_with("did:") {
+        let valid = actor.split(':').count() >= 3 && !actor.ends_with(':');
+        return if valid {
+            Ok(actor.to_string())
+        } else {
+            Err(AppError::InvalidInput(format!("Invalid DID: {}", actor)))
+        };
+    }
+
+    let handle = actor.to_ascii_lowercase();
+    let labels: Vec<&str> = handle.split('.').collect();
+    let valid = labels.len() >= 2
+        && handle.len() <= 253
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+
+    if valid {
+        Ok(handle)
+    } else {
+        Err(AppError::InvalidInput(format!("Invalid handle: {}", actor)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn did_doc(did: &str) -> serde_json::Value {
+        serde_json::json!({
+            "id": did,
+            "alsoKnownAs": ["at://alice.test"],
+            "verificationMethod": [{
+                "id": format!("{}#atproto", did),
+                "type": "Multikey",
+                "controller": did,
+                "publicKeyMultibase": "zQ3shXjHeiBuRCKmM36cuYnm7YEMzhGnCmCyW92sRJ9pribSF"
+            }],
+            "service": [{
+                "id": "#atproto_pds",
+                "type": "AtprotoPersonalDataServer",
+                "serviceEndpoint": "https://pds.example.com"
+            }]
+        })
+    }
+
+    #[test]
+    fn test_normalize_actor() {
+        assert_eq!(normalize_actor("@Alice.BSKY.social").unwrap(), "alice.bsky.social");
+        assert_eq!(normalize_actor("did:plc:abc123").unwrap(), "did:plc:abc123");
+        assert!(normalize_actor("alice").is_err());
+        assert!(normalize_actor("-bad.example.com").is_err());
+        assert!(normalize_actor("did:plc:").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_plc_did() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/did:plc:abc123"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(did_doc("did:plc:abc123")))
+            .mount(&server)
+            .await;
+
+        let resolver = IdentityResolver::new()
+            .unwrap()
+            .with_plc_directory(&server.uri());
+        let document = resolver.resolve_did("did:plc:abc123").await.unwrap();
+
+        assert_eq!(document.pds_endpoint(), Some("https://pds.example.com"));
+        assert!(document.signing_key().unwrap().starts_with('z'));
+        assert_eq!(document.handle(), Some("alice.test"));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_handle_and_web_did() {
+        let server = MockServer::start().await;
+        let host = server.address().to_string();
+        let did = format!("did:web:{}", host.replace(':', "%3A"));
+
+        Mock::given(method("GET"))
+            .and(path("/.well-known/atproto-did"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(format!("{}\n", did)))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/.well-known/did.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(did_doc(&did)))
+            .mount(&server)
+            .await;
+
+        let resolver = IdentityResolver::new()
+            .unwrap()
+            .without_dns()
+            .with_insecure_http();
+
+        assert_eq!(resolver.resolve_handle(&host).await.unwrap(), did);
+        let document = resolver.resolve_did(&did).await.unwrap();
+        assert_eq!(document.id, did);
+    }
+}
@@ -0,0 +1,48 @@
+//! Building blocks for Bluesky automation
+//!
+//! The `bsky-rusty-tools` binary is a thin command-line layer over this crate;
+//! the same pieces can be used from other programs:
+//!
+//! - [`client`]: the XRPC client, with session refresh and rate limiting
+//! - [`auth`]: logging in and managing the stored per-account sessions
+//! - [`api`]: profiles, follower and follow listings, follows and mirroring
+//! - [`db`]: the SQLite snapshot store and the follower diff engine
+//! - [`migrations`]: versioned schema changes for the snapshot store
+//! - [`identity`]: handle and DID resolution
+//! - [`config`]: `config.toml` profiles and the effective [`config::Settings`]
+//! - [`watch`]: the continuous snapshot loop
+//! - [`output`]: rendering results as text, JSON, JSONL, CSV or tables
+//!
+//! Library functions return data and never print; progress is reported
+//! through the `log` crate or callbacks.
+//!
+//! The binary's dependencies (clap, rustyline and the terminal UI) sit behind
+//! the default `cli` and `tui` features; depend on this crate with
+//! `default-features = false` to leave them out.
+//!
+//! ```no_run
+//! use bsky_rusty_tools::api::{get_followers, PageOptions};
+//! use bsky_rusty_tools::client::{Endpoints, XrpcClient};
+//!
+//! # async fn run() -> bsky_rusty_tools::error::Result<()> {
+//! // Uses the session stored by `bsky-rusty-tools login`
+//! let client = XrpcClient::authenticated(&Endpoints::default())?;
+//! for follower in get_followers(&client, PageOptions::default()).await? {
+//!     println!("{} {}", follower.did, follower.handle);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod api;
+pub mod auth;
+pub mod client;
+pub mod config;
+pub mod db;
+pub mod error;
+pub mod identity;
+pub mod migrations;
+pub mod output;
+pub mod ratelimit;
+pub mod utils;
+pub mod watch;
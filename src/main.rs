@@ -1,63 +1,526 @@
-mod api;
-mod auth;
-mod db;
-mod error;
-mod utils;
+mod shell;
+mod terminal;
+#[cfg(feature = "tui")]
+mod tui;
 
-use error::{AppError, Result};
+use bsky_rusty_tools::api::{
+    follow, lookup_profile, mirror_follows, my_profile, resolve_actor_did, unfollow,
+    FollowResult, GraphKind, MirrorOptions, MirrorStatus, UnfollowResult,
+};
+use bsky_rusty_tools::auth::{
+    get_session, list_accounts, logout, remove_account, select_account, switch_account,
+};
+use bsky_rusty_tools::client::XrpcClient;
+use bsky_rusty_tools::config::{data_dir, default_config_path, set_value, Config, ProfileConfig, Settings};
+use bsky_rusty_tools::db::{
+    compare_followers, diff_snapshots, find_snapshot, list_snapshots, migrate_schema, relationship,
+    save_followers, save_following, schema_status, set_db_path, snapshot_view, ProfileLookup, Relationship,
+    SnapshotRef,
+};
+use bsky_rusty_tools::error::{AppError, Result};
+use bsky_rusty_tools::identity::{Identity, IdentityResolver};
+use bsky_rusty_tools::output::{Message, OutputFormat};
+use bsky_rusty_tools::utils::parse_duration;
+use bsky_rusty_tools::watch::{watch, WatchOptions};
+use clap::builder::{PossibleValue, PossibleValuesParser, TypedValueParser};
 use clap::{Parser, Subcommand};
-use crate::auth::{login, get_handle};
-use crate::db::save_followers;
-use log::{info, error};
+use crate::terminal::{confirm, get_handle, login, print, print_error, LoginOptions};
+use log::info;
+use std::path::PathBuf;
+use std::process::ExitCode;
 
 /// BlueSky CLI toolset for automation
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
+    /// Config file (defaults to config.toml in the platform config directory)
+    #[arg(long, global = true, env = "BSKY_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Profile from the config file to use
+    #[arg(long, global = true, env = "BSKY_PROFILE")]
+    profile: Option<String>,
+
+    /// Account (handle or DID) to act as instead of the active one
+    #[arg(long, global = true, env = "BSKY_ACCOUNT")]
+    account: Option<String>,
+
+    /// Handle to log in with
+    #[arg(long, global = true, env = "BSKY_HANDLE")]
+    handle: Option<String>,
+
+    /// SQLite database used to store followers
+    #[arg(long, global = true, env = "BSKY_DB_PATH")]
+    db_path: Option<PathBuf>,
+
+    /// PDS or entryway URL (defaults to the PDS discovered at login)
+    #[arg(long, global = true, env = "BSKY_SERVICE")]
+    service: Option<String>,
+
+    /// AppView URL for reads; logged-in reads reach it through the PDS (defaults to the PDS's own)
+    #[arg(long, global = true, env = "BSKY_APPVIEW")]
+    appview: Option<String>,
+
+    /// PLC directory used to resolve did:plc identifiers
+    #[arg(long, global = true, env = "BSKY_PLC_DIRECTORY")]
+    plc_directory: Option<String>,
+
+    /// Output format for command results [default: text]
+    #[arg(short, long, global = true, value_parser = output_format(), env = "BSKY_OUTPUT")]
+    output: Option<OutputFormat>,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+/// Parses `--output`, listing the formats in `--help`
+fn output_format() -> impl TypedValueParser<Value = OutputFormat> {
+    PossibleValuesParser::new(OutputFormat::ALL.map(|format| {
+        PossibleValue::new(format.as_str()).help(match format {
+            OutputFormat::Text => "Human-readable text",
+            OutputFormat::Json => "A single JSON document",
+            OutputFormat::Jsonl => "One JSON object per line",
+            OutputFormat::Csv => "Comma-separated values with a header row",
+            OutputFormat::Table => "Aligned columns",
+        })
+    }))
+    .map(|format| format.parse::<OutputFormat>().expect("only listed formats get through"))
+}
+
+/// Parses `--kind`; `following` is accepted for `follows`
+fn graph_kind() -> impl TypedValueParser<Value = GraphKind> {
+    PossibleValuesParser::new([
+        PossibleValue::new("followers"),
+        PossibleValue::new("follows").alias("following"),
+    ])
+    .map(|kind| kind.parse::<GraphKind>().expect("only listed kinds get through"))
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Login to BlueSky with your credentials
-    Login,
+    ///
+    /// Without a terminal, the app password must come from BSKY_APP_PASSWORD,
+    /// --password-stdin or --password-file.
+    Login {
+        /// Read the app password from the first line of stdin
+        #[arg(long, conflicts_with = "password_file")]
+        password_stdin: bool,
+        /// Read the app password from a file
+        #[arg(long, env = "BSKY_PASSWORD_FILE")]
+        password_file: Option<PathBuf>,
+        /// Code from the sign-in email, for accounts with email 2FA
+        #[arg(long, env = "BSKY_AUTH_FACTOR_TOKEN", hide_env_values = true)]
+        auth_factor_token: Option<String>,
+    },
+    /// Logout and erase the stored session
+    Logout,
+    /// Show your own profile
+    Profile,
+    /// Show the public profile of a handle or DID
+    Lookup {
+        /// Handle (e.g. user.bsky.social) or DID to look up
+        actor: String,
+    },
+    /// Resolve a handle or DID to its DID document
+    Resolve {
+        /// Handle or DID to resolve
+        actor: String,
+    },
     /// Save your followers to a local database
     SaveFollowers,
+    /// Save the accounts you follow to a local database
+    SaveFollowing,
+    /// Compare your current followers against the saved ones
+    CompareFollowers,
+    /// List accounts that follow you and that you follow back
+    Mutuals,
+    /// List accounts that follow you but that you don't follow back
+    Fans,
+    /// List accounts you follow that don't follow you back
+    NonReciprocal,
+    /// Follow an account
+    Follow {
+        /// Handle or DID to follow
+        actor: String,
+    },
+    /// Unfollow an account
+    Unfollow {
+        /// Handle or DID to unfollow
+        actor: String,
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Follow every account that the given handle follows
+    MirrorFollows {
+        /// Handle whose follows should be mirrored
+        handle: String,
+        /// Show who would be followed without following anyone
+        #[arg(long)]
+        dry_run: bool,
+        /// Follow at most this many accounts
+        #[arg(long, visible_alias = "max")]
+        limit: Option<usize>,
+        /// Follows issued per minute [default: 30]
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        per_minute: Option<u32>,
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Keep snapshotting followers and follows, recording what changed
+    Watch {
+        /// Time between snapshots, e.g. 90s, 15m or 1h
+        #[arg(long, env = "BSKY_WATCH_INTERVAL", default_value = "15m", value_parser = parse_duration)]
+        interval: std::time::Duration,
+        /// File to write a JSON heartbeat to after every snapshot
+        #[arg(long, env = "BSKY_WATCH_HEARTBEAT")]
+        heartbeat: Option<PathBuf>,
+    },
+    /// Open an interactive dashboard of followers, follows and recent changes
+    #[cfg(feature = "tui")]
+    Tui {
+        /// Time between background refreshes, e.g. 90s, 5m or 1h
+        #[arg(long, default_value = "5m", value_parser = parse_duration)]
+        refresh: std::time::Duration,
+    },
+    /// Run commands at an interactive prompt that keeps the session and database open
+    Shell,
+    /// Manage the accounts you are logged in with
+    Accounts {
+        #[command(subcommand)]
+        command: AccountsCommand,
+    },
+    /// Show or change the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Compare two stored snapshots offline
+    Diff {
+        /// Snapshot id, date (YYYY-MM-DD, end of day UTC) or RFC 3339 time to compare from
+        #[arg(long)]
+        from: SnapshotRef,
+        /// Snapshot to compare to, in the same forms or `latest`
+        #[arg(long, default_value = "latest")]
+        to: SnapshotRef,
+        /// List to compare
+        #[arg(long, value_parser = graph_kind(), default_value = "followers")]
+        kind: GraphKind,
+    },
+    /// Browse stored follower and follow snapshots
+    Snapshots {
+        #[command(subcommand)]
+        command: SnapshotsCommand,
+    },
+    /// Inspect or upgrade the follower database schema
+    Db {
+        #[command(subcommand)]
+        command: DbCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum AccountsCommand {
+    /// List stored accounts; the active one is marked with *
+    List,
+    /// Make an account the active one
+    Switch {
+        /// Handle or DID of a stored account
+        account: String,
+    },
+    /// Forget an account's session
+    Remove {
+        /// Handle or DID of a stored account
+        account: String,
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Show the effective settings of the active profile
+    Show,
+    /// Store a setting in the active profile
+    Set {
+        /// Setting to change, e.g. handle, service, db_path, output or per_minute
+        key: String,
+        /// New value
+        value: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum SnapshotsCommand {
+    /// List stored snapshots, oldest first
+    List {
+        /// Only list snapshots of this list
+        #[arg(long, value_parser = graph_kind())]
+        kind: Option<GraphKind>,
+    },
+    /// Show who was in a list at a snapshot or date
+    Show {
+        /// Snapshot id, date (YYYY-MM-DD, end of day UTC) or RFC 3339 time
+        snapshot: SnapshotRef,
+        /// List to show
+        #[arg(long, value_parser = graph_kind(), default_value = "followers")]
+        kind: GraphKind,
+    },
+}
+
+#[derive(Subcommand)]
+enum DbCommand {
+    /// Show the schema version and any pending migrations
+    Status,
+    /// Apply pending migrations
+    Migrate,
+}
+
+/// State shared by the commands of one invocation or `shell` session
+struct Context {
+    settings: Settings,
+    /// Flags the settings were resolved with, kept to re-resolve after `config set`
+    overrides: ProfileConfig,
+    client: Option<XrpcClient>,
+}
+
+impl Context {
+    /// Client for the stored session using the configured endpoints and budget,
+    /// created on first use
+    fn client(&mut self) -> Result<XrpcClient> {
+        if self.client.is_none() {
+            let client = XrpcClient::authenticated(&self.settings.endpoints())?
+                .with_write_budget(self.settings.write_budget());
+            self.client = Some(client);
+        }
+        Ok(self.client.clone().expect("client was just created"))
+    }
+
+    /// Rereads the profile after the config file changed
+    fn reload_settings(&mut self) -> Result<()> {
+        let config = Config::load(&self.settings.config_path)?;
+        self.settings = Settings::resolve(
+            &config,
+            self.settings.config_path.clone(),
+            Some(self.settings.profile.clone()),
+            self.overrides.clone(),
+        )?;
+        // The database and client were opened with the old settings
+        set_db_path(self.settings.db_path.clone());
+        self.client = None;
+        Ok(())
+    }
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
+    // Load BSKY_* (and RUST_LOG) from a .env file before anything reads them
+    dotenvy::dotenv().ok();
+
     // Initialize logging
     env_logger::init();
     info!("Starting bsky-rusty-tools");
 
     let cli = Cli::parse();
+    // Until the profile is loaded, only the flag or BSKY_OUTPUT can pick the format
+    let mut format = cli.output.unwrap_or_default();
+
+    match run(cli, &mut format).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            print_error(&e, format);
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+async fn run(cli: Cli, format: &mut OutputFormat) -> Result<()> {
+    let config_path = match cli.config {
+        Some(path) => path,
+        None => default_config_path()?,
+    };
+    let mut config = Config::load(&config_path)?;
+    // `config set` may name a profile that does not exist yet
+    if let (Commands::Config { command: ConfigCommand::Set { .. } }, Some(profile)) = (&cli.command, &cli.profile) {
+        config.profiles.entry(profile.clone()).or_default();
+    }
+    let overrides = ProfileConfig {
+        handle: cli.handle,
+        service: cli.service,
+        appview: cli.appview,
+        plc_directory: cli.plc_directory,
+        db_path: cli.db_path,
+        output: cli.output,
+        ..Default::default()
+    };
+    let settings = Settings::resolve(&config, config_path, cli.profile, overrides.clone())?;
+    set_db_path(settings.db_path.clone());
+    if let Some(account) = &cli.account {
+        select_account(account)?;
+    }
+
+    *format = settings.output;
+    let format = settings.output;
+    let mut ctx = Context { settings, overrides, client: None };
 
     match cli.command {
-        Commands::Login => {
-            let handle = get_handle()?;
-            match login(&handle).await {
-                Ok(session) => {
-                    info!("Successfully logged in as {}", session.handle);
+        Commands::Shell => shell::run(&mut ctx).await,
+        command => execute(command, &mut ctx, format).await,
+    }
+}
+
+/// Runs one command, reusing the client in `ctx` if one was created before
+async fn execute(command: Commands, ctx: &mut Context, format: OutputFormat) -> Result<()> {
+    let endpoints = ctx.settings.endpoints();
+
+    match command {
+        Commands::Login { password_stdin, password_file, auth_factor_token } => {
+            let handle = get_handle(&ctx.settings)?;
+            let options = LoginOptions { password_stdin, password_file, auth_factor_token };
+            let session = login(&handle, &endpoints, &options).await?;
+            ctx.client = None;
+            info!("Successfully logged in as {}", session.handle);
+            print(&Message::new(format!("Logged in as @{}", session.handle)), format)?;
+        }
+        Commands::Logout => {
+            logout()?;
+            ctx.client = None;
+            print(&Message::new("Logged out"), format)?;
+        }
+        Commands::Profile => {
+            let client = ctx.client()?;
+            print(&my_profile(&client).await?, format)?;
+        }
+        Commands::Lookup { actor } => {
+            let client = ctx.client()?;
+            let profile = lookup_profile(&client, &actor).await?;
+            print(&ProfileLookup::new(&client.session()?.did, profile)?, format)?;
+        }
+        Commands::Resolve { actor } => {
+            let resolver = IdentityResolver::for_endpoints(&endpoints)?;
+            let document = resolver.resolve(&actor).await?;
+            print(&Identity::from(&document), format)?;
+        }
+        Commands::SaveFollowers => {
+            let client = ctx.client()?;
+            let saved = save_followers(&client).await?;
+            info!("Successfully saved followers");
+            print(&saved, format)?;
+        }
+        Commands::SaveFollowing => {
+            let client = ctx.client()?;
+            print(&save_following(&client).await?, format)?;
+        }
+        Commands::Mutuals => print(&relationship(&get_session()?.did, Relationship::Mutuals)?, format)?,
+        Commands::Fans => print(&relationship(&get_session()?.did, Relationship::Fans)?, format)?,
+        Commands::NonReciprocal => {
+            print(&relationship(&get_session()?.did, Relationship::NonReciprocal)?, format)?
+        }
+        Commands::CompareFollowers => {
+            let client = ctx.client()?;
+            print(&compare_followers(&client).await?, format)?;
+        }
+        Commands::Follow { actor } => {
+            let client = ctx.client()?;
+            let did = resolve_actor_did(&client, &actor).await?;
+            let record = follow(&client, &did).await?;
+            print(&FollowResult { actor, did, record }, format)?;
+        }
+        Commands::Unfollow { actor, yes } => {
+            if !yes && !confirm(&format!("Unfollow {}?", actor))? {
+                print(&Message::new("Aborted"), format)?;
+                return Ok(());
+            }
+
+            let client = ctx.client()?;
+            let did = resolve_actor_did(&client, &actor).await?;
+            let record = unfollow(&client, &did).await?;
+            print(&UnfollowResult { actor, did, record }, format)?;
+        }
+        Commands::MirrorFollows { handle, dry_run, limit, per_minute, yes } => {
+            if !dry_run && !yes && !confirm(&format!("Follow everyone @{} follows?", handle))? {
+                print(&Message::new("Aborted"), format)?;
+                return Ok(());
+            }
+
+            let client = ctx.client()?;
+            let per_minute = per_minute.unwrap_or(ctx.settings.per_minute);
+            let options = MirrorOptions { dry_run, max: limit, per_minute };
+            let report = mirror_follows(&client, &handle, options, |i, total, entry| {
+                match entry.status {
+                    MirrorStatus::Failed => eprintln!("[{}/{}] Failed to follow @{}", i, total, entry.handle),
+                    _ => eprintln!("[{}/{}] Followed @{}", i, total, entry.handle),
                 }
-                Err(e) => {
-                    error!("Login failed: {}", e);
-                    return Err(AppError::Auth(format!("Login failed: {}", e)));
+            })
+            .await?;
+            info!("Finished mirroring follows of {}", handle);
+            print(&report, format)?;
+        }
+        Commands::Watch { interval, heartbeat } => {
+            let client = ctx.client()?;
+            let heartbeat = match heartbeat {
+                Some(path) => path,
+                None => data_dir()?.join("watch-heartbeat.json"),
+            };
+            watch(&client, WatchOptions { interval, heartbeat }, |changes| print(changes, format)).await?;
+        }
+        #[cfg(feature = "tui")]
+        Commands::Tui { refresh } => {
+            let client = ctx.client()?;
+            tui::run(client, tui::TuiOptions { refresh }).await?;
+        }
+        Commands::Shell => {
+            return Err(AppError::InvalidInput("Already in the shell".to_string()));
+        }
+        Commands::Accounts { command } => match command {
+            AccountsCommand::List => print(&list_accounts()?, format)?,
+            AccountsCommand::Switch { account } => {
+                let session = switch_account(&account)?;
+                ctx.client = None;
+                print(&Message::new(format!("Switched to @{}", session.handle)), format)?;
+            }
+            AccountsCommand::Remove { account, yes } => {
+                if !yes && !confirm(&format!("Remove the session for {}?", account))? {
+                    print(&Message::new("Aborted"), format)?;
+                    return Ok(());
                 }
+                let session = remove_account(&account)?;
+                ctx.client = None;
+                print(&Message::new(format!("Removed @{}", session.handle)), format)?;
+            }
+        },
+        Commands::Config { command } => match command {
+            ConfigCommand::Show => print(&ctx.settings, format)?,
+            ConfigCommand::Set { key, value } => {
+                set_value(&ctx.settings.config_path, &ctx.settings.profile, &key, &value)?;
+                ctx.reload_settings()?;
+                print(
+                    &Message::new(format!("Set {} = {} in profile {}", key, value, ctx.settings.profile)),
+                    format,
+                )?;
             }
+        },
+        Commands::Diff { from, to, kind } => {
+            let owner_did = get_session()?.did;
+            print(&diff_snapshots(&owner_did, kind, from, to)?, format)?;
         }
-        Commands::SaveFollowers => {
-            match save_followers().await {
-                Ok(_) => {
-                    info!("Successfully saved followers");
-                }
-                Err(e) => {
-                    error!("Failed to save followers: {}", e);
-                    return Err(AppError::Api(format!("Failed to save followers: {}", e)));
+        Commands::Snapshots { command } => {
+            let owner_did = get_session()?.did;
+            match command {
+                SnapshotsCommand::List { kind } => print(&list_snapshots(&owner_did, kind)?, format)?,
+                SnapshotsCommand::Show { snapshot, kind } => {
+                    let snapshot = find_snapshot(&owner_did, kind, snapshot)?;
+                    print(&snapshot_view(snapshot)?, format)?;
                 }
             }
         }
+        Commands::Db { command } => match command {
+            DbCommand::Status => print(&schema_status()?, format)?,
+            DbCommand::Migrate => print(&migrate_schema()?, format)?,
+        },
     }
 
     Ok(())
@@ -0,0 +1,282 @@
+//! Versioned schema changes for the follower database
+//!
+//! The schema version lives in `PRAGMA user_version`. Each migration runs in
+//! its own transaction together with the version bump, so an interrupted
+//! upgrade leaves the database at the last completed step.
+
+use crate::error::{AppError, Result};
+use log::info;
+use rusqlite::Connection;
+use serde::Serialize;
+
+/// One step of the schema history
+pub struct Migration {
+    /// Value of `user_version` once this step has run
+    pub version: u32,
+    pub description: &'static str,
+    apply: fn(&Connection) -> rusqlite::Result<()>,
+}
+
+/// Every migration, oldest first; append new steps and never edit old ones
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Create followers, follower_diffs, config and handles",
+        apply: create_initial_tables,
+    },
+    Migration {
+        version: 2,
+        description: "Scope followers and follower diffs to the owning account",
+        apply: add_owner_columns,
+    },
+    Migration {
+        version: 3,
+        description: "Create follows",
+        apply: create_follows,
+    },
+    Migration {
+        version: 4,
+        description: "Keep every follower and follow snapshot instead of only the latest",
+        apply: create_snapshots,
+    },
+    Migration {
+        version: 5,
+        description: "Record the previous handle of renamed accounts",
+        apply: add_old_handle,
+    },
+];
+
+/// Version the newest migration brings a database to
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |migration| migration.version)
+}
+
+/// Version recorded in the database; 0 for new databases and ones that predate migrations
+pub fn schema_version(conn: &Connection) -> rusqlite::Result<u32> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+/// Migrations the database has not had yet
+pub fn pending(conn: &Connection) -> Result<&'static [Migration]> {
+    let version = schema_version(conn)?;
+    if version > latest_version() {
+        return Err(AppError::SchemaTooNew {
+            version,
+            supported: latest_version(),
+        });
+    }
+    let applied = MIGRATIONS.iter().take_while(|m| m.version <= version).count();
+    Ok(&MIGRATIONS[applied..])
+}
+
+/// Applies every pending migration and returns the ones that ran
+pub fn migrate(conn: &Connection) -> Result<Vec<&'static Migration>> {
+    let pending = pending(conn)?;
+    for migration in pending {
+        info!("Migrating database to version {}: {}", migration.version, migration.description);
+        let tx = conn.unchecked_transaction()?;
+        (migration.apply)(&tx)?;
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+    }
+    Ok(pending.iter().collect())
+}
+
+/// A migration as shown by `db status` and `db migrate`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MigrationInfo {
+    pub version: u32,
+    pub description: String,
+}
+
+impl From<&Migration> for MigrationInfo {
+    fn from(migration: &Migration) -> Self {
+        Self {
+            version: migration.version,
+            description: migration.description.to_string(),
+        }
+    }
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> rusqlite::Result<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let columns = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(columns.iter().any(|name| name == column))
+}
+
+/// The layout from before versioning; databases of that age already have it
+fn create_initial_tables(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS followers (
+            did TEXT PRIMARY KEY,
+            handle TEXT NOT NULL,
+            indexed_at DATETIME NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS follower_diffs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            did TEXT NOT NULL,
+            handle TEXT NOT NULL,
+            action TEXT NOT NULL,
+            timestamp DATETIME NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS config (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS handles (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            handle TEXT NOT NULL
+        );",
+    )
+}
+
+/// Adds `owner_did`; existing rows keep an empty owner until an account adopts them
+///
+/// Unversioned databases written by multi-account builds already have the
+/// columns, so each one is only added when missing.
+fn add_owner_columns(conn: &Connection) -> rusqlite::Result<()> {
+    if !has_column(conn, "followers", "owner_did")? {
+        conn.execute_batch(
+            "ALTER TABLE followers RENAME TO followers_single;
+            CREATE TABLE followers (
+                owner_did TEXT NOT NULL DEFAULT '',
+                did TEXT NOT NULL,
+                handle TEXT NOT NULL,
+                indexed_at DATETIME NOT NULL,
+                PRIMARY KEY (owner_did, did)
+            );
+            INSERT INTO followers (did, handle, indexed_at)
+                SELECT did, handle, indexed_at FROM followers_single;
+            DROP TABLE followers_single;",
+        )?;
+    }
+    if !has_column(conn, "follower_diffs", "owner_did")? {
+        conn.execute(
+            "ALTER TABLE follower_diffs ADD COLUMN owner_did TEXT NOT NULL DEFAULT ''",
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+fn create_follows(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS follows (
+            owner_did TEXT NOT NULL,
+            did TEXT NOT NULL,
+            handle TEXT NOT NULL,
+            indexed_at DATETIME NOT NULL,
+            PRIMARY KEY (owner_did, did)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Moves the stored lists into `snapshots` and turns `followers` and `follows`
+/// into views of each account's latest snapshot
+///
+/// The stored lists carry no snapshot time, so the newest `indexed_at` of each
+/// one stands in for it.
+fn create_snapshots(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE snapshots (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            owner_did TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            taken_at DATETIME NOT NULL,
+            count INTEGER NOT NULL
+        );
+        CREATE INDEX snapshots_by_owner ON snapshots (owner_did, kind, id);
+        CREATE TABLE snapshot_members (
+            snapshot_id INTEGER NOT NULL REFERENCES snapshots (id),
+            did TEXT NOT NULL,
+            handle TEXT NOT NULL,
+            indexed_at DATETIME NOT NULL,
+            PRIMARY KEY (snapshot_id, did)
+        );",
+    )?;
+
+    // Each list is stored under the name of its old table
+    for kind in ["followers", "follows"] {
+        conn.execute_batch(&format!(
+            "INSERT INTO snapshots (owner_did, kind, taken_at, count)
+                SELECT owner_did, '{kind}', MAX(indexed_at), COUNT(*) FROM {kind} GROUP BY owner_did;
+            INSERT INTO snapshot_members (snapshot_id, did, handle, indexed_at)
+                SELECT s.id, t.did, t.handle, t.indexed_at
+                FROM {kind} t JOIN snapshots s ON s.owner_did = t.owner_did AND s.kind = '{kind}';
+            DROP TABLE {kind};
+            CREATE VIEW {kind} AS
+                SELECT s.owner_did, m.did, m.handle, m.indexed_at
+                FROM snapshot_members m JOIN snapshots s ON s.id = m.snapshot_id
+                WHERE s.id IN (SELECT MAX(id) FROM snapshots WHERE kind = '{kind}' GROUP BY owner_did);"
+        ))?;
+    }
+    Ok(())
+}
+
+fn add_old_handle(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute("ALTER TABLE follower_diffs ADD COLUMN old_handle TEXT", [])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrations_are_ordered() {
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version as usize, i + 1);
+        }
+    }
+
+    #[test]
+    fn test_migrate_new_database() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert_eq!(pending(&conn).unwrap().len(), MIGRATIONS.len());
+
+        assert_eq!(migrate(&conn).unwrap().len(), MIGRATIONS.len());
+        assert_eq!(schema_version(&conn).unwrap(), latest_version());
+        assert!(has_column(&conn, "followers", "owner_did").unwrap());
+        assert!(has_column(&conn, "follows", "owner_did").unwrap());
+        assert!(migrate(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_migrate_keeps_unversioned_data() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_initial_tables(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO followers VALUES ('did:plc:a', 'a.test', '2024-01-01T00:00:00Z');
+            INSERT INTO follower_diffs (did, handle, action, timestamp)
+                VALUES ('did:plc:a', 'a.test', 'follow', '2024-01-01T00:00:00Z');",
+        )
+        .unwrap();
+
+        migrate(&conn).unwrap();
+
+        let row: (String, String) = conn
+            .query_row("SELECT owner_did, handle FROM followers", [], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert_eq!(row, (String::new(), "a.test".to_string()));
+        let snapshot: (String, String, i64) = conn
+            .query_row("SELECT kind, taken_at, count FROM snapshots", [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .unwrap();
+        assert_eq!(snapshot, ("followers".to_string(), "2024-01-01T00:00:00Z".to_string(), 1));
+        let diffs: i64 = conn.query_row("SELECT COUNT(*) FROM follower_diffs", [], |row| row.get(0)).unwrap();
+        assert_eq!(diffs, 1);
+    }
+
+    #[test]
+    fn test_newer_schema_is_refused() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", latest_version() + 1).unwrap();
+        assert!(matches!(pending(&conn), Err(AppError::SchemaTooNew { .. })));
+        assert!(migrate(&conn).is_err());
+    }
+}
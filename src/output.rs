@@ -0,0 +1,286 @@
+use crate::error::{AppError, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// How command results are written to stdout
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// A single JSON document
+    Json,
+    /// One JSON object per line
+    Jsonl,
+    /// Comma-separated values with a header row
+    Csv,
+    /// Aligned columns
+    Table,
+}
+
+impl OutputFormat {
+    pub const ALL: [OutputFormat; 5] = [
+        OutputFormat::Text,
+        OutputFormat::Json,
+        OutputFormat::Jsonl,
+        OutputFormat::Csv,
+        OutputFormat::Table,
+    ];
+
+    /// Name of the format, as used by `--output` and `config.toml`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OutputFormat::Text => "text",
+            OutputFormat::Json => "json",
+            OutputFormat::Jsonl => "jsonl",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Table => "table",
+        }
+    }
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    /// Accepts a format name, ignoring case
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|format| format.as_str().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| format!("unknown output format: {}", s))
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A command result that every output format can render
+pub trait Render: Serialize {
+    /// Human-readable rendering used by `--output text`
+    fn text(&self) -> String;
+
+    /// Rows used by the line-oriented formats (jsonl, csv, table)
+    ///
+    /// Defaults to the whole result as a single row; list-like results
+    /// override this to return one row per item.
+    fn records(&self) -> Result<Vec<Value>> {
+        Ok(vec![serde_json::to_value(self)?])
+    }
+}
+
+/// Renders `result` in `format`, without a trailing newline
+pub fn render<R: Render + ?Sized>(result: &R, format: OutputFormat) -> Result<String> {
+    Ok(match format {
+        OutputFormat::Text => result.text(),
+        OutputFormat::Json => serde_json::to_string_pretty(result)?,
+        OutputFormat::Jsonl => result
+            .records()?
+            .iter()
+            .map(serde_json::to_string)
+            .collect::<std::result::Result<Vec<_>, _>>()?
+            .join("\n"),
+        OutputFormat::Csv => {
+            let (columns, rows) = tabulate(&result.records()?);
+            std::iter::once(columns)
+                .chain(rows)
+                .map(|row| row.iter().map(|cell| csv_escape(cell)).collect::<Vec<_>>().join(","))
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+        OutputFormat::Table => {
+            let (columns, rows) = tabulate(&result.records()?);
+            format_table(&columns, &rows)
+        }
+    })
+}
+
+/// Structured form of `error` written to stderr by the JSON formats
+pub fn error_envelope(error: &AppError) -> Value {
+    let category = error.category();
+    let mut details = serde_json::json!({
+        "category": category,
+        "exit_code": category.exit_code(),
+        "message": error.to_string(),
+    });
+    if let AppError::Xrpc(e) = error {
+        details["xrpc"] = serde_json::json!({
+            "error": e.kind.to_string(),
+            "status": e.status,
+            "message": e.message,
+        });
+    }
+    serde_json::json!({ "error": details })
+}
+
+/// Flattens records into a header row and string cells
+///
+/// Columns follow the key order of the records; nested values are written as
+/// compact JSON and missing or null values as empty cells.
+fn tabulate(records: &[Value]) -> (Vec<String>, Vec<Vec<String>>) {
+    let empty = Map::new();
+    let objects: Vec<&Map<String, Value>> = records
+        .iter()
+        .map(|record| record.as_object().unwrap_or(&empty))
+        .collect();
+
+    let mut columns: Vec<String> = Vec::new();
+    for object in &objects {
+        for key in object.keys() {
+            if !columns.contains(key) {
+                columns.push(key.clone());
+            }
+        }
+    }
+
+    let rows = objects
+        .iter()
+        .map(|object| {
+            columns
+                .iter()
+                .map(|column| match object.get(column) {
+                    None | Some(Value::Null) => String::new(),
+                    Some(Value::String(s)) => s.clone(),
+                    Some(other) => other.to_string(),
+                })
+                .collect()
+        })
+        .collect();
+
+    (columns, rows)
+}
+
+fn csv_escape(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+fn format_table(columns: &[String], rows: &[Vec<String>]) -> String {
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain(std::iter::once(column.chars().count()))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let format_row = |cells: &[String]| {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    let header = columns.iter().map(|c| c.to_uppercase()).collect::<Vec<_>>();
+    std::iter::once(format_row(&header))
+        .chain(rows.iter().map(|row| format_row(row)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A result that only carries a status message
+#[derive(Debug, Serialize)]
+pub struct Message {
+    pub message: String,
+}
+
+impl Message {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+}
+
+impl Render for Message {
+    fn text(&self) -> String {
+        self.message.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_format_names() {
+        for format in OutputFormat::ALL {
+            assert_eq!(format.to_string().parse::<OutputFormat>(), Ok(format));
+        }
+        assert_eq!("CSV".parse::<OutputFormat>(), Ok(OutputFormat::Csv));
+        assert!("xml".parse::<OutputFormat>().is_err());
+    }
+
+    #[derive(Serialize)]
+    struct Accounts {
+        accounts: Vec<Value>,
+    }
+
+    impl Render for Accounts {
+        fn text(&self) -> String {
+            format!("{} accounts", self.accounts.len())
+        }
+
+        fn records(&self) -> Result<Vec<Value>> {
+            Ok(self.accounts.clone())
+        }
+    }
+
+    fn sample() -> Accounts {
+        Accounts {
+            accounts: vec![
+                serde_json::json!({"did": "did:plc:a", "handle": "a.test", "note": "hi, there"}),
+                serde_json::json!({"did": "did:plc:bb", "handle": "bb.test", "note": null}),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_render_line_formats() {
+        let result = sample();
+
+        assert_eq!(render(&result, OutputFormat::Text).unwrap(), "2 accounts");
+        assert_eq!(
+            render(&result, OutputFormat::Csv).unwrap(),
+            "did,handle,note\ndid:plc:a,a.test,\"hi, there\"\ndid:plc:bb,bb.test,"
+        );
+        assert_eq!(
+            render(&result, OutputFormat::Jsonl).unwrap().lines().count(),
+            2
+        );
+        assert_eq!(
+            render(&result, OutputFormat::Table).unwrap(),
+            "DID         HANDLE   NOTE\ndid:plc:a   a.test   hi, there\ndid:plc:bb  bb.test"
+        );
+    }
+
+    #[test]
+    fn test_error_envelope() {
+        let envelope = error_envelope(&AppError::Auth("Not logged in".to_string()));
+        assert_eq!(envelope["error"]["category"], "auth");
+        assert_eq!(envelope["error"]["exit_code"], 4);
+        assert!(envelope["error"].get("xrpc").is_none());
+    }
+
+    #[test]
+    fn test_render_json_keeps_structure() {
+        let rendered = render(&sample(), OutputFormat::Json).unwrap();
+        let value: Value = serde_json::from_str(&rendered).unwrap();
+        assert_eq!(value["accounts"][1]["handle"], "bb.test");
+    }
+}
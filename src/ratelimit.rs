@@ -0,0 +1,278 @@
+use crate::error::{AppError, RateLimitInfo, Result};
+use crate::utils::rate_limit;
+use log::info;
+use rand::Rng;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const HOUR: Duration = Duration::from_secs(60 * 60);
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Longest single backoff after a 429
+const MAX_BACKOFF: Duration = Duration::from_secs(120);
+
+/// Per-account write budget, in PDS rate-limit points
+#[derive(Debug, Clone, Copy)]
+pub struct WriteBudget {
+    pub points_per_hour: u32,
+    pub points_per_day: u32,
+}
+
+impl Default for WriteBudget {
+    /// The limits bsky.social applies to repo writes
+    fn default() -> Self {
+        Self {
+            points_per_hour: 5_000,
+            points_per_day: 35_000,
+        }
+    }
+}
+
+/// Points the PDS charges for a repo write; everything else is free
+pub fn write_cost(nsid: &str) -> u32 {
+    match nsid {
+        "com.atproto.repo.createRecord" | "com.atproto.repo.applyWrites" => 3,
+        "com.atproto.repo.putRecord" => 2,
+        "com.atproto.repo.deleteRecord" => 1,
+        _ => 0,
+    }
+}
+
+#[derive(Debug)]
+struct Window {
+    length: Duration,
+    started: Instant,
+    used: u32,
+}
+
+impl Window {
+    fn new(length: Duration, now: Instant) -> Self {
+        Self {
+            length,
+            started: now,
+            used: 0,
+        }
+    }
+
+    fn roll(&mut self, now: Instant) {
+        if now.duration_since(self.started) >= self.length {
+            self.started = now;
+            self.used = 0;
+        }
+    }
+
+    /// How long until `cost` more points fit under `limit`, or `None` if they never will
+    fn wait_for(&self, cost: u32, limit: u32, now: Instant) -> Option<Duration> {
+        if cost > limit {
+            None
+        } else if self.used + cost <= limit {
+            Some(Duration::ZERO)
+        } else {
+            Some((self.started + self.length).saturating_duration_since(now))
+        }
+    }
+}
+
+#[derive(Debug)]
+struct AccountState {
+    server: RateLimitInfo,
+    hourly: Window,
+    daily: Window,
+}
+
+impl AccountState {
+    fn new(now: Instant) -> Self {
+        Self {
+            server: RateLimitInfo::default(),
+            hourly: Window::new(HOUR, now),
+            daily: Window::new(DAY, now),
+        }
+    }
+}
+
+/// Schedules requests so they stay within the server's and our own budgets
+///
+/// The server's `ratelimit-*` headers are remembered per account, and writes
+/// are additionally charged against a local hourly and daily points budget.
+#[derive(Debug)]
+pub struct RateLimiter {
+    budget: WriteBudget,
+    accounts: Mutex<HashMap<String, AccountState>>,
+}
+
+impl RateLimiter {
+    pub fn new(budget: WriteBudget) -> Self {
+        Self {
+            budget,
+            accounts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Waits until `nsid` may be sent for `account`, then charges its cost
+    ///
+    /// Fails instead of waiting when the request costs more than a whole
+    /// window of the budget allows.
+    pub async fn acquire(&self, account: &str, nsid: &str) -> Result<()> {
+        loop {
+            let delay = self.reserve(account, nsid, Instant::now(), chrono::Utc::now().timestamp())?;
+            if delay.is_zero() {
+                return Ok(());
+            }
+            info!(
+                "Rate limit budget for {} exhausted, waiting {}s before {}",
+                account,
+                delay.as_secs(),
+                nsid
+            );
+            rate_limit(delay).await;
+        }
+    }
+
+    /// Remembers the rate-limit headers of the latest response for `account`
+    pub fn record(&self, account: &str, info: RateLimitInfo) {
+        if info == RateLimitInfo::default() {
+            return;
+        }
+        let mut accounts = self.accounts.lock().unwrap_or_else(|e| e.into_inner());
+        accounts
+            .entry(account.to_string())
+            .or_insert_with(|| AccountState::new(Instant::now()))
+            .server = info;
+    }
+
+    /// Charges the request and returns zero, or returns how long to wait first
+    fn reserve(&self, account: &str, nsid: &str, now: Instant, now_unix: i64) -> Result<Duration> {
+        let mut accounts = self.accounts.lock().unwrap_or_else(|e| e.into_inner());
+        let state = accounts
+            .entry(account.to_string())
+            .or_insert_with(|| AccountState::new(now));
+
+        // Server says the window is spent: wait for its reset
+        if state.server.remaining == Some(0) {
+            if let Some(reset) = state.server.reset {
+                if reset > now_unix {
+                    return Ok(Duration::from_secs((reset - now_unix) as u64));
+                }
+            }
+            state.server.remaining = None;
+        }
+
+        let cost = write_cost(nsid);
+        if cost == 0 {
+            return Ok(Duration::ZERO);
+        }
+
+        state.hourly.roll(now);
+        state.daily.roll(now);
+        let hourly = state.hourly.wait_for(cost, self.budget.points_per_hour, now);
+        let daily = state.daily.wait_for(cost, self.budget.points_per_day, now);
+        let delay = hourly.zip(daily).map(|(hourly, daily)| hourly.max(daily)).ok_or_else(|| {
+            AppError::Config(format!(
+                "{} costs {} points but the write budget allows {} per hour and {} per day",
+                nsid, cost, self.budget.points_per_hour, self.budget.points_per_day
+            ))
+        })?;
+        if delay.is_zero() {
+            state.hourly.used += cost;
+            state.daily.used += cost;
+        }
+        Ok(delay)
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(WriteBudget::default())
+    }
+}
+
+/// Delay before retry `attempt` (starting at 0) after a 429
+///
+/// Exponential from one second with full jitter, but never sooner than the
+/// server's reset time when it told us one.
+pub fn backoff(attempt: u32, info: &RateLimitInfo) -> Duration {
+    let ceiling = Duration::from_secs(1u64 << attempt.min(7)).min(MAX_BACKOFF);
+    let jittered = rand::thread_rng().gen_range(ceiling / 2..=ceiling);
+
+    let until_reset = info
+        .reset
+        .map(|reset| reset - chrono::Utc::now().timestamp())
+        .filter(|secs| *secs > 0)
+        .map(|secs| Duration::from_secs(secs as u64))
+        .unwrap_or_default();
+
+    jittered.max(until_reset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_write_budget() {
+        let limiter = RateLimiter::new(WriteBudget {
+            points_per_hour: 5,
+            points_per_day: 100,
+        });
+        let now = Instant::now();
+
+        assert!(limiter.reserve("did:plc:a", "com.atproto.repo.createRecord", now, 0).unwrap().is_zero());
+        assert!(!limiter.reserve("did:plc:a", "com.atproto.repo.createRecord", now, 0).unwrap().is_zero());
+        // Reads are free and other accounts have their own budget
+        assert!(limiter.reserve("did:plc:a", "app.bsky.actor.getProfile", now, 0).unwrap().is_zero());
+        assert!(limiter.reserve("did:plc:b", "com.atproto.repo.createRecord", now, 0).unwrap().is_zero());
+        // The hourly window rolls over
+        assert!(limiter
+            .reserve("did:plc:a", "com.atproto.repo.createRecord", now + HOUR, 0)
+            .unwrap()
+            .is_zero());
+    }
+
+    #[test]
+    fn test_write_larger_than_budget_fails() {
+        let now = Instant::now();
+        for budget in [
+            WriteBudget { points_per_hour: 0, points_per_day: 100 },
+            WriteBudget { points_per_hour: 100, points_per_day: 2 },
+        ] {
+            let limiter = RateLimiter::new(budget);
+            assert!(matches!(
+                limiter.reserve("did:plc:a", "com.atproto.repo.createRecord", now, 0),
+                Err(AppError::Config(_))
+            ));
+            // Free requests still go through
+            assert!(limiter.reserve("did:plc:a", "app.bsky.actor.getProfile", now, 0).unwrap().is_zero());
+        }
+    }
+
+    #[test]
+    fn test_server_reported_exhaustion() {
+        let limiter = RateLimiter::default();
+        limiter.record(
+            "did:plc:a",
+            RateLimitInfo {
+                remaining: Some(0),
+                reset: Some(1_000),
+                ..Default::default()
+            },
+        );
+
+        let now = Instant::now();
+        assert_eq!(
+            limiter.reserve("did:plc:a", "app.bsky.actor.getProfile", now, 990).unwrap(),
+            Duration::from_secs(10)
+        );
+        assert!(limiter.reserve("did:plc:a", "app.bsky.actor.getProfile", now, 1_001).unwrap().is_zero());
+    }
+
+    #[test]
+    fn test_backoff_bounds() {
+        let info = RateLimitInfo::default();
+        for attempt in 0..10 {
+            let delay = backoff(attempt, &info);
+            assert!(delay >= Duration::from_millis(500));
+            assert!(delay <= MAX_BACKOFF);
+        }
+    }
+}
@@ -0,0 +1,219 @@
+//! Interactive prompt for the `shell` command
+//!
+//! Every line is parsed like the arguments of a normal invocation and run
+//! against the same [`Context`], so the session, client and database
+//! connection are set up once for the whole session.
+
+use crate::terminal::print_error;
+use crate::{execute, output_format, Cli, Commands, Context};
+use bsky_rusty_tools::api::GraphKind;
+use bsky_rusty_tools::auth::get_session;
+use bsky_rusty_tools::config::data_dir;
+use bsky_rusty_tools::db::stored_accounts;
+use bsky_rusty_tools::error::Result;
+use bsky_rusty_tools::output::OutputFormat;
+use clap::{CommandFactory, Parser};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::history::FileHistory;
+use rustyline::{Editor, Helper, Highlighter, Hinter, Validator};
+
+/// Commands whose positional argument is an account
+const ACTOR_COMMANDS: &[&str] = &["lookup", "resolve", "follow", "unfollow", "mirror-follows"];
+
+/// Words the shell handles itself
+const BUILTINS: &[&str] = &["exit", "quit", "help"];
+
+/// One line typed at the prompt
+#[derive(Parser)]
+#[command(name = "shell", no_binary_name = true, disable_version_flag = true)]
+struct ShellLine {
+    /// Output format for this command [default: the profile's]
+    #[arg(short, long, value_parser = output_format())]
+    output: Option<OutputFormat>,
+
+    #[command(subcommand)]
+    command: Commands,
+}
+
+/// Tab completion of command names and of handles from the local database
+#[derive(Helper, Hinter, Highlighter, Validator)]
+struct ShellHelper {
+    /// Top-level commands with their subcommands
+    commands: Vec<(String, Vec<String>)>,
+    /// Followers and follows stored for the current account
+    handles: Vec<String>,
+}
+
+impl ShellHelper {
+    fn new(handles: Vec<String>) -> Self {
+        let commands = Cli::command()
+            .get_subcommands()
+            .filter(|command| command.get_name() != "shell")
+            .map(|command| {
+                let subcommands = command.get_subcommands().map(|sub| sub.get_name().to_string()).collect();
+                (command.get_name().to_string(), subcommands)
+            })
+            .chain(BUILTINS.iter().map(|builtin| (builtin.to_string(), Vec::new())))
+            .collect();
+        Self { commands, handles }
+    }
+
+    /// Where the word being completed starts in `line`, and what it could become
+    fn candidates(&self, line: &str) -> (usize, Vec<String>) {
+        let start = line.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let word = &line[start..];
+        let previous: Vec<&str> = line[..start].split_whitespace().collect();
+
+        let pool: Vec<String> = match previous.as_slice() {
+            [] => self.commands.iter().map(|(name, _)| name.clone()).collect(),
+            [command] if self.subcommands(command).is_some_and(|subs| !subs.is_empty()) => {
+                self.subcommands(command).unwrap_or_default().to_vec()
+            }
+            _ if word.starts_with('@') => self.handles.iter().map(|handle| format!("@{}", handle)).collect(),
+            [command, ..] if ACTOR_COMMANDS.contains(command) && !word.starts_with('-') => self.handles.clone(),
+            _ => Vec::new(),
+        };
+
+        let prefix = word.to_lowercase();
+        let mut matches: Vec<String> = pool
+            .into_iter()
+            .filter(|candidate| candidate.to_lowercase().starts_with(&prefix))
+            .collect();
+        matches.sort();
+        matches.dedup();
+        (start, matches)
+    }
+
+    fn subcommands(&self, command: &str) -> Option<&[String]> {
+        self.commands
+            .iter()
+            .find(|(name, _)| name == command)
+            .map(|(_, subcommands)| subcommands.as_slice())
+    }
+}
+
+impl Completer for ShellHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &rustyline::Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.candidates(&line[..pos]))
+    }
+}
+
+/// Handles of the stored followers and follows of the current account
+fn known_handles() -> Vec<String> {
+    let Ok(session) = get_session() else {
+        return Vec::new();
+    };
+    let mut handles: Vec<String> = [GraphKind::Followers, GraphKind::Follows]
+        .into_iter()
+        .flat_map(|kind| stored_accounts(&session.did, kind).unwrap_or_default())
+        .map(|account| account.handle)
+        .collect();
+    handles.sort();
+    handles.dedup();
+    handles
+}
+
+fn prompt() -> String {
+    match get_session() {
+        Ok(session) => format!("bsky @{}> ", session.handle),
+        Err(_) => "bsky> ".to_string(),
+    }
+}
+
+/// Reads and runs commands until `exit`, `quit` or end of input
+///
+/// A failing command reports its error and the prompt carries on.
+pub async fn run(ctx: &mut Context) -> Result<()> {
+    let mut editor: Editor<ShellHelper, FileHistory> = Editor::new()
+        .map_err(|e| anyhow::anyhow!("Failed to start the shell: {}", e))?;
+    editor.set_helper(Some(ShellHelper::new(known_handles())));
+
+    let history = data_dir()?.join("shell_history");
+    // There is no history before the first session
+    let _ = editor.load_history(&history);
+
+    loop {
+        let prompt = prompt();
+        let line = match tokio::task::block_in_place(|| editor.readline(&prompt)) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(anyhow::anyhow!("Failed to read from the terminal: {}", e).into()),
+        };
+
+        let Some(words) = shlex::split(&line) else {
+            eprintln!("Error: unbalanced quotes");
+            continue;
+        };
+        match words.first().map(String::as_str) {
+            None => continue,
+            Some("exit" | "quit") => break,
+            Some(_) => {
+                let _ = editor.add_history_entry(line.as_str());
+            }
+        }
+
+        match ShellLine::try_parse_from(words) {
+            Ok(parsed) => {
+                let format = parsed.output.unwrap_or(ctx.settings.output);
+                if let Err(e) = execute(parsed.command, ctx, format).await {
+                    print_error(&e, format);
+                }
+            }
+            // Usage errors and `help` output alike
+            Err(e) => {
+                let _ = e.print();
+            }
+        }
+
+        // Logins, account switches and snapshots change what can be completed
+        if let Some(helper) = editor.helper_mut() {
+            helper.handles = known_handles();
+        }
+    }
+
+    editor
+        .save_history(&history)
+        .map_err(|e| anyhow::anyhow!("Failed to save {}: {}", history.display(), e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn helper() -> ShellHelper {
+        ShellHelper::new(vec!["alice.test".to_string(), "bob.test".to_string()])
+    }
+
+    #[test]
+    fn test_completes_commands_and_subcommands() {
+        let helper = helper();
+        assert_eq!(helper.candidates("com"), (0, vec!["compare-followers".to_string()]));
+        assert_eq!(helper.candidates("ex"), (0, vec!["exit".to_string()]));
+        assert!(!helper.candidates("").1.contains(&"shell".to_string()));
+        assert_eq!(
+            helper.candidates("accounts s"),
+            (9, vec!["switch".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_completes_handles_for_actor_arguments() {
+        let helper = helper();
+        assert_eq!(helper.candidates("follow A"), (7, vec!["alice.test".to_string()]));
+        assert_eq!(helper.candidates("lookup @b"), (7, vec!["@bob.test".to_string()]));
+        assert_eq!(helper.candidates("unfollow --"), (9, vec![]));
+        assert_eq!(helper.candidates("profile a"), (8, vec![]));
+    }
+
+    #[test]
+    fn test_parses_lines_like_arguments() {
+        let line = ShellLine::try_parse_from(shlex::split("-o json lookup 'alice.test'").unwrap()).unwrap();
+        assert_eq!(line.output, Some(OutputFormat::Json));
+        assert!(matches!(line.command, Commands::Lookup { actor } if actor == "alice.test"));
+    }
+}
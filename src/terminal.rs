@@ -0,0 +1,155 @@
+//! Prompts and printing for the command-line binary
+
+use bsky_rusty_tools::auth::{create_session, Session};
+use bsky_rusty_tools::client::Endpoints;
+use bsky_rusty_tools::config::{set_value, Settings};
+use bsky_rusty_tools::db::get_saved_handle;
+use bsky_rusty_tools::error::{AppError, Result, XrpcErrorKind};
+use bsky_rusty_tools::output::{error_envelope, render, OutputFormat, Render};
+use log::info;
+use rpassword::prompt_password;
+use std::fs;
+use std::io::{IsTerminal, Write};
+use std::path::PathBuf;
+
+/// Environment variable holding the app password for non-interactive logins
+pub const APP_PASSWORD_ENV: &str = "BSKY_APP_PASSWORD";
+
+/// Renders `result` in `format` and prints it to stdout
+pub fn print<R: Render + ?Sized>(result: &R, format: OutputFormat) -> Result<()> {
+    let rendered = render(result, format)?;
+    if !rendered.is_empty() {
+        println!("{}", rendered);
+    }
+    Ok(())
+}
+
+/// Reports `error` on stderr: as an envelope for json and jsonl, as a line otherwise
+pub fn print_error(error: &AppError, format: OutputFormat) {
+    match format {
+        OutputFormat::Json => match serde_json::to_string_pretty(&error_envelope(error)) {
+            Ok(json) => eprintln!("{}", json),
+            Err(_) => eprintln!("Error: {}", error),
+        },
+        OutputFormat::Jsonl => eprintln!("{}", error_envelope(error)),
+        _ => eprintln!("Error: {}", error),
+    }
+}
+
+fn is_interactive() -> bool {
+    std::io::stdin().is_terminal()
+}
+
+/// Asks a yes/no question on stdin; anything but "y" or "yes" means no
+///
+/// The question goes to stderr so it never mixes with machine-readable output.
+pub fn confirm(question: &str) -> std::io::Result<bool> {
+    eprint!("{} [y/N] ", question);
+    std::io::stderr().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Where `login` takes its secrets from when not prompting
+#[derive(Debug, Clone, Default)]
+pub struct LoginOptions {
+    /// Read the app password from the first line of stdin
+    pub password_stdin: bool,
+    /// Read the app password from this file
+    pub password_file: Option<PathBuf>,
+    /// Email 2FA code, sent with the first attempt
+    pub auth_factor_token: Option<String>,
+}
+
+/// Picks the app password from a file, stdin, `BSKY_APP_PASSWORD` or a prompt, in that order
+fn read_password(options: &LoginOptions) -> Result<String> {
+    let password = if let Some(path) = &options.password_file {
+        fs::read_to_string(path).map_err(|e| {
+            AppError::Auth(format!("Failed to read password file {}: {}", path.display(), e))
+        })?
+    } else if options.password_stdin {
+        let mut password = String::new();
+        std::io::stdin().read_line(&mut password)?;
+        password
+    } else if let Ok(password) = std::env::var(APP_PASSWORD_ENV) {
+        password
+    } else if is_interactive() {
+        prompt_password("Enter your app password: ")?
+    } else {
+        return Err(AppError::Auth(format!(
+            "No app password available without a terminal: set {}, or use --password-stdin or --password-file",
+            APP_PASSWORD_ENV
+        )));
+    };
+
+    let password = password.trim().to_string();
+    if password.is_empty() {
+        return Err(AppError::Auth("App password is empty".to_string()));
+    }
+    Ok(password)
+}
+
+/// Logs in, prompting for whatever `options` does not supply
+pub async fn login(handle: &str, endpoints: &Endpoints, options: &LoginOptions) -> Result<Session> {
+    let password = read_password(options)?;
+    let result = create_session(handle, &password, options.auth_factor_token.as_deref(), endpoints).await;
+
+    // Handle email verification if required
+    match result {
+        Err(e) if e.xrpc_kind() == Some(&XrpcErrorKind::AuthFactorTokenRequired) => {
+            if options.auth_factor_token.is_some() {
+                return Err(AppError::Auth("The email verification code was rejected".to_string()));
+            }
+            if !is_interactive() {
+                return Err(AppError::Auth(
+                    "This account requires an email verification code: rerun with --auth-factor-token"
+                        .to_string(),
+                ));
+            }
+            let auth_factor_token = prompt_password("Enter the verification code sent to your email: ")?;
+            create_session(handle, &password, Some(auth_factor_token.trim()), endpoints).await
+        }
+        other => other,
+    }
+}
+
+/// Returns the configured handle, asking for one and saving it if unset
+pub fn get_handle(settings: &Settings) -> Result<String> {
+    if let Some(handle) = &settings.handle {
+        info!("Using configured handle: {}", handle);
+        return Ok(handle.clone());
+    }
+
+    // Fall back to the handle older versions kept in the database
+    let handle = match get_saved_handle()? {
+        Some(handle) => {
+            info!("Using saved handle: {}", handle);
+            handle
+        }
+        None if !is_interactive() => {
+            return Err(AppError::Auth(
+                "No handle configured: pass --handle or set BSKY_HANDLE".to_string(),
+            ));
+        }
+        None => {
+            eprint!("Enter your BlueSky handle (e.g. user.bsky.social): ");
+            std::io::stderr().flush()?;
+
+            let mut handle = String::new();
+            std::io::stdin().read_line(&mut handle)?;
+            let handle = handle.trim().to_string();
+            if handle.is_empty() {
+                return Err(AppError::InvalidInput("No handle given".to_string()));
+            }
+            handle
+        }
+    };
+
+    // Remember the handle in the profile for future use
+    set_value(&settings.config_path, &settings.profile, "handle", &handle)?;
+    info!("Saved handle {} to profile {}", handle, settings.profile);
+
+    Ok(handle)
+}
@@ -0,0 +1,575 @@
+//! Full-screen dashboard for the `tui` command
+//!
+//! Lists come from the local database so the dashboard opens instantly; a
+//! background task snapshots the graph through the API on every refresh and
+//! reloads them.
+
+use bsky_rusty_tools::api::{block, follow, my_profile, unfollow, GraphKind, Profile};
+use bsky_rusty_tools::client::XrpcClient;
+use bsky_rusty_tools::db::{recent_diffs, snapshot_graph, stored_accounts, Account, DiffEvent};
+use bsky_rusty_tools::error::Result;
+use bsky_rusty_tools::utils::parse_timestamp;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, List, ListState, Paragraph, Tabs};
+use ratatui::{DefaultTerminal, Frame};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+/// Number of follower and follow events shown in the events tab
+const EVENT_LIMIT: usize = 200;
+
+/// How long the key reader waits for input before checking whether to stop
+const KEY_POLL: Duration = Duration::from_millis(100);
+
+/// Settings for the `tui` command
+#[derive(Debug, Clone)]
+pub struct TuiOptions {
+    /// Time between background snapshots
+    pub refresh: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tab {
+    Followers,
+    Following,
+    Events,
+}
+
+impl Tab {
+    const ALL: [Tab; 3] = [Tab::Followers, Tab::Following, Tab::Events];
+
+    fn title(self) -> &'static str {
+        match self {
+            Tab::Followers => "Followers",
+            Tab::Following => "Following",
+            Tab::Events => "Events",
+        }
+    }
+
+    fn index(self) -> usize {
+        Self::ALL.iter().position(|&tab| tab == self).unwrap_or(0)
+    }
+
+    fn next(self) -> Self {
+        Self::ALL[(self.index() + 1) % Self::ALL.len()]
+    }
+
+    fn previous(self) -> Self {
+        Self::ALL[(self.index() + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+}
+
+/// Something the dashboard asks the event loop to do
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Action {
+    Follow(Account),
+    Unfollow(Account),
+    Block(Account),
+    Open(Account),
+    Refresh,
+}
+
+/// What a background task reports back to the dashboard
+#[derive(Debug)]
+enum Update {
+    Data(Box<Dashboard>),
+    Status(String),
+    RefreshFailed(String),
+}
+
+/// Everything the dashboard shows apart from its own UI state
+#[derive(Debug, Default)]
+struct Dashboard {
+    profile: Option<Profile>,
+    followers: Vec<Account>,
+    following: Vec<Account>,
+    events: Vec<DiffEvent>,
+}
+
+impl Dashboard {
+    /// Reads the lists stored for `owner_did` by the last snapshot
+    fn load(owner_did: &str) -> Result<Self> {
+        Ok(Self {
+            profile: None,
+            followers: stored_accounts(owner_did, GraphKind::Followers)?,
+            following: stored_accounts(owner_did, GraphKind::Follows)?,
+            events: recent_diffs(owner_did, EVENT_LIMIT)?,
+        })
+    }
+}
+
+/// Dashboard state, kept apart from the terminal so key handling can be tested
+#[derive(Debug)]
+struct App {
+    handle: String,
+    data: Dashboard,
+    tab: Tab,
+    selected: usize,
+    search: String,
+    searching: bool,
+    /// Action waiting for a `y` before it runs
+    pending: Option<Action>,
+    refreshing: bool,
+    status: String,
+    quit: bool,
+}
+
+impl App {
+    fn new(handle: String, data: Dashboard) -> Self {
+        Self {
+            handle,
+            data,
+            tab: Tab::Followers,
+            selected: 0,
+            search: String::new(),
+            searching: false,
+            pending: None,
+            refreshing: false,
+            status: String::new(),
+            quit: false,
+        }
+    }
+
+    /// Accounts and labels of the current tab that match the search
+    fn rows(&self) -> Vec<(Account, String)> {
+        let rows: Vec<(Account, String)> = match self.tab {
+            Tab::Followers => self.data.followers.iter().map(|a| (a.clone(), format!("@{}", a.handle))).collect(),
+            Tab::Following => self.data.following.iter().map(|a| (a.clone(), format!("@{}", a.handle))).collect(),
+            Tab::Events => self
+                .data
+                .events
+                .iter()
+                .map(|event| {
+                    let account = Account { did: event.did.clone(), handle: event.handle.clone() };
+                    (account, event_line(event))
+                })
+                .collect(),
+        };
+
+        let needle = self.search.to_lowercase();
+        rows.into_iter()
+            .filter(|(account, _)| {
+                needle.is_empty()
+                    || account.handle.to_lowercase().contains(&needle)
+                    || account.did.to_lowercase().contains(&needle)
+            })
+            .collect()
+    }
+
+    fn selected_account(&self) -> Option<Account> {
+        self.rows().into_iter().nth(self.selected).map(|(account, _)| account)
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let len = self.rows().len();
+        self.selected = match len {
+            0 => 0,
+            _ => self.selected.saturating_add_signed(delta).min(len - 1),
+        };
+    }
+
+    fn switch_tab(&mut self, tab: Tab) {
+        self.tab = tab;
+        self.selected = 0;
+    }
+
+    /// Applies a key press, returning the action it triggers, if any
+    fn handle_key(&mut self, key: KeyEvent) -> Option<Action> {
+        if let Some(action) = self.pending.take() {
+            if matches!(key.code, KeyCode::Char('y') | KeyCode::Char('Y')) {
+                return Some(action);
+            }
+            self.status = "Cancelled".to_string();
+            return None;
+        }
+
+        if self.searching {
+            match key.code {
+                KeyCode::Esc => {
+                    self.search.clear();
+                    self.searching = false;
+                }
+                KeyCode::Enter => self.searching = false,
+                KeyCode::Backspace => {
+                    self.search.pop();
+                }
+                KeyCode::Char(c) => self.search.push(c),
+                _ => {}
+            }
+            self.selected = 0;
+            return None;
+        }
+
+        match key.code {
+            KeyCode::Char('q') => self.quit = true,
+            KeyCode::Char('j') | KeyCode::Down => self.move_selection(1),
+            KeyCode::Char('k') | KeyCode::Up => self.move_selection(-1),
+            KeyCode::PageDown => self.move_selection(10),
+            KeyCode::PageUp => self.move_selection(-10),
+            KeyCode::Tab | KeyCode::Right => self.switch_tab(self.tab.next()),
+            KeyCode::BackTab | KeyCode::Left => self.switch_tab(self.tab.previous()),
+            KeyCode::Char('/') => self.searching = true,
+            KeyCode::Esc => {
+                self.search.clear();
+                self.selected = 0;
+            }
+            KeyCode::Char('r') => return Some(Action::Refresh),
+            KeyCode::Char('f') => return self.selected_account().map(Action::Follow),
+            KeyCode::Char('o') | KeyCode::Enter => return self.selected_account().map(Action::Open),
+            KeyCode::Char('u') => self.ask(Action::Unfollow, "Unfollow"),
+            KeyCode::Char('b') => self.ask(Action::Block, "Block"),
+            _ => {}
+        }
+        None
+    }
+
+    /// Holds an action on the selected account until it is confirmed
+    fn ask(&mut self, action: fn(Account) -> Action, verb: &str) {
+        if let Some(account) = self.selected_account() {
+            self.status = format!("{} @{}? [y/N]", verb, account.handle);
+            self.pending = Some(action(account));
+        }
+    }
+
+    fn apply(&mut self, update: Update) {
+        match update {
+            Update::Data(data) => {
+                let profile = data.profile.or(self.data.profile.take());
+                self.data = Dashboard { profile, ..*data };
+                self.move_selection(0);
+                self.refreshing = false;
+                self.status = format!("Refreshed at {}", chrono::Local::now().format("%H:%M:%S"));
+            }
+            Update::Status(status) => self.status = status,
+            Update::RefreshFailed(error) => {
+                self.refreshing = false;
+                self.status = format!("Refresh failed: {}", error);
+            }
+        }
+    }
+}
+
+/// One line of the events tab
+fn event_line(event: &DiffEvent) -> String {
+    let what = match event.action.as_str() {
+        "follow" => format!("@{} followed you", event.handle),
+        "unfollow" => format!("@{} unfollowed you", event.handle),
+        "start_following" => format!("You followed @{}", event.handle),
+        "stop_following" => format!("You unfollowed @{}", event.handle),
+        "handle_change" => format!(
+            "@{} is now @{}",
+            event.old_handle.as_deref().unwrap_or("?"),
+            event.handle
+        ),
+        other => format!("{} @{}", other, event.handle),
+    };
+    let when = parse_timestamp(&event.timestamp)
+        .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|_| event.timestamp.clone());
+    format!("{}  {}", when, what)
+}
+
+/// Runs the dashboard until the user quits
+pub async fn run(client: XrpcClient, options: TuiOptions) -> Result<()> {
+    let session = client.session()?;
+    let mut app = App::new(session.handle.clone(), Dashboard::load(&session.did)?);
+
+    let mut terminal = ratatui::init();
+    let (keys_tx, keys) = unbounded_channel();
+    let reader = KeyReader::spawn(keys_tx);
+    let result = event_loop(&mut terminal, &mut app, keys, client, &session.did, options).await;
+    // Stopped before the terminal is restored, so the shell gets the next key
+    drop(reader);
+    ratatui::restore();
+    result
+}
+
+/// Reads keys on a thread of its own, since crossterm reads block
+///
+/// The thread polls so it can notice it should stop; dropping the reader
+/// stops it and waits for it to finish.
+struct KeyReader {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl KeyReader {
+    fn spawn(keys: UnboundedSender<KeyEvent>) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let thread = std::thread::spawn(move || {
+            while !stopped.load(Ordering::Relaxed) {
+                match event::poll(KEY_POLL) {
+                    Ok(false) => continue,
+                    Ok(true) => {}
+                    Err(_) => break,
+                }
+                match event::read() {
+                    Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => {
+                        if keys.send(key).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(_) => break,
+                }
+            }
+        });
+        Self { stop, thread: Some(thread) }
+    }
+}
+
+impl Drop for KeyReader {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+async fn event_loop(
+    terminal: &mut DefaultTerminal,
+    app: &mut App,
+    mut keys: UnboundedReceiver<KeyEvent>,
+    client: XrpcClient,
+    owner_did: &str,
+    options: TuiOptions,
+) -> Result<()> {
+    let (updates_tx, mut updates) = unbounded_channel();
+    let mut ticker = tokio::time::interval(options.refresh);
+
+    while !app.quit {
+        terminal.draw(|frame| draw(frame, app))?;
+
+        let action = tokio::select! {
+            Some(key) = keys.recv() => app.handle_key(key),
+            Some(update) = updates.recv() => {
+                app.apply(update);
+                None
+            }
+            _ = ticker.tick() => Some(Action::Refresh),
+        };
+
+        match action {
+            Some(Action::Refresh) if !app.refreshing => {
+                app.refreshing = true;
+                app.status = "Refreshing...".to_string();
+                spawn_refresh(client.clone(), owner_did.to_string(), updates_tx.clone());
+            }
+            Some(Action::Refresh) | None => {}
+            Some(Action::Open(account)) => {
+                let url = format!("https://bsky.app/profile/{}", account.handle);
+                app.status = match open::that_detached(&url) {
+                    Ok(()) => format!("Opened {}", url),
+                    Err(e) => format!("Failed to open {}: {}", url, e),
+                };
+            }
+            Some(action) => spawn_action(client.clone(), action, updates_tx.clone()),
+        }
+    }
+    Ok(())
+}
+
+/// Snapshots the graph and profile, then reloads the lists from the database
+fn spawn_refresh(client: XrpcClient, owner_did: String, updates: UnboundedSender<Update>) {
+    tokio::spawn(async move {
+        let refresh = async {
+            snapshot_graph(&client).await?;
+            let profile = my_profile(&client).await?;
+            let data = Dashboard { profile: Some(profile), ..Dashboard::load(&owner_did)? };
+            Result::Ok(data)
+        };
+        let update = match refresh.await {
+            Ok(data) => Update::Data(Box::new(data)),
+            Err(e) => Update::RefreshFailed(e.to_string()),
+        };
+        let _ = updates.send(update);
+    });
+}
+
+/// Runs a follow, unfollow or block and reports how it went
+fn spawn_action(client: XrpcClient, action: Action, updates: UnboundedSender<Update>) {
+    tokio::spawn(async move {
+        let status = match action {
+            Action::Follow(account) => match follow(&client, &account.did).await {
+                Ok(_) => format!("Followed @{}", account.handle),
+                Err(e) => format!("Failed to follow @{}: {}", account.handle, e),
+            },
+            Action::Unfollow(account) => match unfollow(&client, &account.did).await {
+                Ok(Some(_)) => format!("Unfollowed @{}", account.handle),
+                Ok(None) => format!("Not following @{}", account.handle),
+                Err(e) => format!("Failed to unfollow @{}: {}", account.handle, e),
+            },
+            Action::Block(account) => match block(&client, &account.did).await {
+                Ok(_) => format!("Blocked @{}", account.handle),
+                Err(e) => format!("Failed to block @{}: {}", account.handle, e),
+            },
+            Action::Open(_) | Action::Refresh => return,
+        };
+        let _ = updates.send(Update::Status(status));
+    });
+}
+
+fn draw(frame: &mut Frame, app: &App) {
+    let [header, tabs, body, footer] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Length(1),
+        Constraint::Min(1),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    let counts = match &app.data.profile {
+        Some(profile) => format!(
+            "{} followers · {} following",
+            profile.followers_count, profile.follows_count
+        ),
+        None => format!(
+            "{} followers · {} following (stored)",
+            app.data.followers.len(),
+            app.data.following.len()
+        ),
+    };
+    frame.render_widget(
+        Paragraph::new(Line::from(vec![format!("@{}  ", app.handle).bold(), counts.into()]))
+            .block(Block::default().borders(Borders::ALL).title(" bsky-rusty-tools ")),
+        header,
+    );
+
+    frame.render_widget(
+        Tabs::new(Tab::ALL.iter().map(|tab| tab.title()))
+            .select(app.tab.index())
+            .highlight_style(Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED)),
+        tabs,
+    );
+
+    let rows = app.rows();
+    let title = match (app.searching, app.search.is_empty()) {
+        (true, _) => format!(" {} ({}) /{}_ ", app.tab.title(), rows.len(), app.search),
+        (false, false) => format!(" {} ({}) /{} ", app.tab.title(), rows.len(), app.search),
+        (false, true) => format!(" {} ({}) ", app.tab.title(), rows.len()),
+    };
+    let list = List::new(rows.into_iter().map(|(_, label)| label))
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = ListState::default().with_selected(Some(app.selected));
+    frame.render_stateful_widget(list, body, &mut state);
+
+    let help = "q quit  / search  tab switch  f follow  u unfollow  b block  o open  r refresh";
+    let footer_text = match app.status.is_empty() {
+        true => help.to_string(),
+        false => format!("{}  |  {}", app.status, help),
+    };
+    frame.render_widget(Paragraph::new(footer_text).dim(), footer);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::crossterm::event::KeyModifiers;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn account(handle: &str) -> Account {
+        Account { did: format!("did:plc:{}", handle), handle: format!("{}.test", handle) }
+    }
+
+    fn app() -> App {
+        App::new(
+            "me.test".to_string(),
+            Dashboard {
+                followers: vec![account("alice"), account("bob"), account("carol")],
+                following: vec![account("dave")],
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_key_reader_ends_when_dropped() {
+        let (keys_tx, mut keys) = unbounded_channel();
+        drop(KeyReader::spawn(keys_tx));
+        // The thread has finished and dropped its sender, so nothing reads keys any more
+        assert_eq!(keys.try_recv(), Err(tokio::sync::mpsc::error::TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn test_search_filters_current_tab() {
+        let mut app = app();
+        app.handle_key(key(KeyCode::Char('/')));
+        for c in "BO".chars() {
+            app.handle_key(key(KeyCode::Char(c)));
+        }
+        app.handle_key(key(KeyCode::Enter));
+        assert_eq!(app.selected_account(), Some(account("bob")));
+        assert_eq!(app.rows().len(), 1);
+
+        // Keys act on the list again once the search is closed
+        assert_eq!(app.handle_key(key(KeyCode::Char('f'))), Some(Action::Follow(account("bob"))));
+
+        app.handle_key(key(KeyCode::Esc));
+        assert_eq!(app.rows().len(), 3);
+    }
+
+    #[test]
+    fn test_navigation_stays_in_bounds() {
+        let mut app = app();
+        app.handle_key(key(KeyCode::Up));
+        assert_eq!(app.selected, 0);
+        for _ in 0..5 {
+            app.handle_key(key(KeyCode::Char('j')));
+        }
+        assert_eq!(app.selected_account(), Some(account("carol")));
+
+        app.handle_key(key(KeyCode::Tab));
+        assert_eq!(app.tab, Tab::Following);
+        assert_eq!(app.selected_account(), Some(account("dave")));
+
+        app.handle_key(key(KeyCode::BackTab));
+        app.handle_key(key(KeyCode::BackTab));
+        assert_eq!(app.tab, Tab::Events);
+        assert_eq!(app.selected_account(), None);
+        assert_eq!(app.handle_key(key(KeyCode::Char('o'))), None);
+    }
+
+    #[test]
+    fn test_destructive_actions_need_confirmation() {
+        let mut app = app();
+        assert_eq!(app.handle_key(key(KeyCode::Char('u'))), None);
+        assert_eq!(app.handle_key(key(KeyCode::Char('n'))), None);
+        assert_eq!(app.status, "Cancelled");
+
+        app.handle_key(key(KeyCode::Char('j')));
+        assert_eq!(app.handle_key(key(KeyCode::Char('b'))), None);
+        assert_eq!(app.status, "Block @bob.test? [y/N]");
+        assert_eq!(app.handle_key(key(KeyCode::Char('y'))), Some(Action::Block(account("bob"))));
+    }
+
+    #[test]
+    fn test_event_lines() {
+        let event = DiffEvent {
+            did: "did:plc:alice".to_string(),
+            handle: "alice.test".to_string(),
+            action: "unfollow".to_string(),
+            old_handle: None,
+            timestamp: "not a time".to_string(),
+        };
+        assert_eq!(event_line(&event), "not a time  @alice.test unfollowed you");
+
+        let event = DiffEvent {
+            action: "handle_change".to_string(),
+            old_handle: Some("al.test".to_string()),
+            ..event
+        };
+        assert_eq!(event_line(&event), "not a time  @al.test is now @alice.test");
+    }
+}
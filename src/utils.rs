@@ -2,23 +2,57 @@ use chrono::{DateTime, Utc};
 use std::time::Duration;
 use anyhow::Result;
 
-// These functions are kept for future use but marked as #[allow(dead_code)]
-#[allow(dead_code)]
+/// Formats a timestamp as RFC 3339, the format used throughout the AT Protocol
 pub fn format_timestamp(dt: DateTime<Utc>) -> String {
     dt.to_rfc3339()
 }
 
-#[allow(dead_code)]
+/// Parses an RFC 3339 timestamp into UTC
 pub fn parse_timestamp(s: &str) -> Result<DateTime<Utc>> {
     Ok(DateTime::parse_from_rfc3339(s)?.with_timezone(&Utc))
 }
 
-#[allow(dead_code)]
 pub async fn rate_limit(duration: Duration) {
     tokio::time::sleep(duration).await;
 }
 
-#[allow(dead_code)]
-pub fn handle_api_error(status: u16, body: &str) -> anyhow::Error {
-    anyhow::anyhow!("API error (status {}): {}", status, body)
-} 
\ No newline at end of file
+/// Parses durations like `90`, `30s`, `15m`, `2h` or `1d`; bare numbers are seconds
+///
+/// Zero is rejected: every duration taken on the command line paces a loop
+/// that would otherwise hammer the API.
+pub fn parse_duration(value: &str) -> std::result::Result<Duration, String> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration: {}", value))?;
+
+    let seconds = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("unknown unit in {} (use s, m, h or d)", value)),
+    };
+    if number == 0 {
+        return Err(format!("duration must be greater than zero, got {}", value));
+    }
+    Ok(Duration::from_secs(number.saturating_mul(seconds)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("15m"), Ok(Duration::from_secs(900)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("0").is_err());
+        assert!(parse_duration("0m").is_err());
+        assert!(parse_duration("5 weeks").is_err());
+    }
+}
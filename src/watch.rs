@@ -0,0 +1,162 @@
+use crate::client::XrpcClient;
+use crate::db::{snapshot_graph, GraphChanges};
+use crate::error::Result;
+use chrono::{DateTime, Utc};
+use log::{error, info, warn};
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// First retry delay after a failed snapshot; doubles up to the interval
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Settings for the `watch` command
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    /// Time between successful snapshots
+    pub interval: Duration,
+    /// File rewritten after every snapshot attempt
+    pub heartbeat: PathBuf,
+}
+
+/// Liveness record written to the heartbeat file for supervisors and monitoring
+#[derive(Debug, Default, Serialize)]
+struct Heartbeat {
+    pid: u32,
+    account: String,
+    updated_at: Option<DateTime<Utc>>,
+    last_success: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+    consecutive_failures: u32,
+    snapshots: u64,
+    stopped: bool,
+}
+
+impl Heartbeat {
+    /// Writes the heartbeat through a temporary file so readers never see half of it
+    fn write(&mut self, path: &PathBuf) -> Result<()> {
+        self.updated_at = Some(Utc::now());
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+}
+
+/// Snapshots followers and follows every `interval` until SIGTERM or Ctrl-C
+///
+/// `on_changes` receives the baseline snapshot and every later snapshot that
+/// found changes; an error from it stops the watch.
+///
+/// Failed snapshots are logged and retried with a growing delay (capped at the
+/// interval) rather than ending the loop; expired access tokens are refreshed
+/// by the client itself.
+pub async fn watch(
+    client: &XrpcClient,
+    options: WatchOptions,
+    mut on_changes: impl FnMut(&GraphChanges) -> Result<()>,
+) -> Result<()> {
+    let mut heartbeat = Heartbeat {
+        pid: std::process::id(),
+        account: client.session()?.handle,
+        ..Default::default()
+    };
+    heartbeat.write(&options.heartbeat)?;
+    info!(
+        "Watching @{} every {}s",
+        heartbeat.account,
+        options.interval.as_secs()
+    );
+
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+
+    loop {
+        let outcome = tokio::select! {
+            outcome = snapshot_graph(client) => outcome,
+            _ = &mut shutdown => break,
+        };
+
+        let delay = match outcome {
+            Ok(changes) => {
+                heartbeat.snapshots += 1;
+                heartbeat.last_success = Some(Utc::now());
+                heartbeat.consecutive_failures = 0;
+                if !changes.is_empty() || !changes.baseline.is_empty() {
+                    on_changes(&changes)?;
+                }
+                options.interval
+            }
+            Err(e) => {
+                heartbeat.consecutive_failures += 1;
+                heartbeat.last_error = Some(e.to_string());
+                let delay = retry_delay(heartbeat.consecutive_failures, options.interval);
+                warn!("Snapshot failed ({}), retrying in {}s", e, delay.as_secs());
+                delay
+            }
+        };
+
+        if let Err(e) = heartbeat.write(&options.heartbeat) {
+            error!("Failed to write heartbeat: {}", e);
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = &mut shutdown => break,
+        }
+    }
+
+    info!("Shutting down watch");
+    heartbeat.stopped = true;
+    heartbeat.write(&options.heartbeat)
+}
+
+/// Delay before retry number `failures` (starting at 1)
+fn retry_delay(failures: u32, interval: Duration) -> Duration {
+    RETRY_DELAY
+        .saturating_mul(1 << failures.saturating_sub(1).min(16))
+        .min(interval)
+}
+
+/// Resolves on SIGTERM (as sent by systemd) or Ctrl-C
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                tokio::select! {
+                    _ = sigterm.recv() => {}
+                    _ = tokio::signal::ctrl_c() => {}
+                }
+            }
+            Err(e) => {
+                warn!("Cannot listen for SIGTERM: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay_grows_up_to_interval() {
+        let interval = Duration::from_secs(300);
+        assert_eq!(retry_delay(1, interval), Duration::from_secs(30));
+        assert_eq!(retry_delay(3, interval), Duration::from_secs(120));
+        assert_eq!(retry_delay(10, interval), interval);
+        assert_eq!(retry_delay(u32::MAX, interval), interval);
+    }
+}
@@ -0,0 +1,1066 @@
+mod common;
+
+use common::{account, stderr, stdout, FakePds, MY_DID, MY_HANDLE};
+use rusqlite::Connection;
+
+const GET_FOLLOWERS: &str = "app.bsky.graph.getFollowers";
+const GET_FOLLOWS: &str = "app.bsky.graph.getFollows";
+
+fn stored_followers(pds: &FakePds) -> Vec<String> {
+    let conn = Connection::open(pds.db_path()).unwrap();
+    let mut stmt = conn.prepare("SELECT handle FROM followers ORDER BY handle").unwrap();
+    let handles = stmt
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<Vec<String>, _>>()
+        .unwrap();
+    handles
+}
+
+#[tokio::test]
+async fn test_save_followers_follows_every_page() {
+    let pds = FakePds::start().await;
+    pds.login();
+    pds.mount_graph(
+        GET_FOLLOWERS,
+        MY_DID,
+        vec![
+            vec![account("did:plc:a", "a.test"), account("did:plc:b", "b.test")],
+            vec![account("did:plc:c", "c.test")],
+        ],
+    )
+    .await;
+
+    let output = pds.run(&["save-followers"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Saved 3 followers"));
+    assert_eq!(stored_followers(&pds), ["a.test", "b.test", "c.test"]);
+}
+
+#[tokio::test]
+async fn test_save_followers_pages_past_empty_page() {
+    let pds = FakePds::start().await;
+    pds.login();
+    pds.mount_graph(
+        GET_FOLLOWERS,
+        MY_DID,
+        vec![
+            vec![account("did:plc:a", "a.test")],
+            vec![],
+            vec![account("did:plc:b", "b.test")],
+        ],
+    )
+    .await;
+
+    let output = pds.run(&["save-followers"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stored_followers(&pds), ["a.test", "b.test"]);
+}
+
+#[tokio::test]
+async fn test_graph_is_fetched_by_did_after_rename() {
+    let pds = FakePds::start().await;
+    // The stored handle is only updated on the next token refresh
+    pds.login_as(MY_DID, "old-me.test");
+    pds.mount_graph(GET_FOLLOWERS, MY_DID, vec![vec![account("did:plc:a", "a.test")]])
+        .await;
+
+    let output = pds.run(&["save-followers"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stored_followers(&pds), ["a.test"]);
+}
+
+#[tokio::test]
+async fn test_expired_token_is_refreshed() {
+    let pds = FakePds::start().await;
+    pds.login();
+    pds.mount_session().await;
+    pds.expire_first_token().await;
+    pds.mount_graph(GET_FOLLOWERS, MY_DID, vec![vec![account("did:plc:a", "a.test")]])
+        .await;
+
+    let output = pds.run(&["save-followers"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stored_followers(&pds), ["a.test"]);
+
+    let session = pds.read_session();
+    assert_eq!(session["access_jwt"], "access-2");
+    assert_eq!(session["refresh_jwt"], "refresh-2");
+}
+
+#[tokio::test]
+async fn test_save_followers_requires_login() {
+    let pds = FakePds::start().await;
+
+    let output = pds.run(&["save-followers"]).await;
+    assert_eq!(output.status.code(), Some(4));
+    assert!(stderr(&output).starts_with("Error: "), "{}", stderr(&output));
+}
+
+#[tokio::test]
+async fn test_errors_have_exit_codes_and_json_envelope() {
+    let pds = FakePds::start().await;
+    pds.login();
+
+    let output = pds.run(&["lookup", "not a handle", "--output", "json"]).await;
+    assert_eq!(output.status.code(), Some(3));
+    assert!(output.stdout.is_empty());
+    let envelope: serde_json::Value = serde_json::from_str(&stderr(&output)).unwrap();
+    assert_eq!(envelope["error"]["category"], "invalid_input");
+    assert_eq!(envelope["error"]["exit_code"], 3);
+
+    // Unmocked endpoints answer 404, which keeps its XRPC details
+    let output = pds.run(&["profile", "-o", "jsonl"]).await;
+    assert_eq!(output.status.code(), Some(9));
+    let envelope: serde_json::Value = serde_json::from_str(stderr(&output).trim()).unwrap();
+    assert_eq!(envelope["error"]["category"], "api");
+    assert_eq!(envelope["error"]["xrpc"]["status"], 404);
+
+    let output = pds.run(&["--profile", "missing", "profile"]).await;
+    assert_eq!(output.status.code(), Some(8));
+}
+
+async fn mount_mirror_fixture(pds: &FakePds) {
+    pds.login();
+    pds.mount_graph(
+        GET_FOLLOWS,
+        "curated.test",
+        vec![
+            vec![
+                account(MY_DID, MY_HANDLE),
+                account("did:plc:known", "known.test"),
+                serde_json::json!({
+                    "did": "did:plc:blocked",
+                    "handle": "blocked.test",
+                    "viewer": { "blocking": "at://did:plc:me/app.bsky.graph.block/1" }
+                }),
+            ],
+            vec![
+                account("did:plc:gone", "gone.test"),
+                account("did:plc:new1", "new1.test"),
+                account("did:plc:new2", "new2.test"),
+            ],
+        ],
+    )
+    .await;
+    pds.mount_graph(GET_FOLLOWS, MY_DID, vec![vec![account("did:plc:known", "known.test")]])
+        .await;
+    pds.mount_profiles(vec![
+        account("did:plc:new1", "new1.test"),
+        account("did:plc:new2", "new2.test"),
+    ])
+    .await;
+}
+
+#[tokio::test]
+async fn test_mirror_follows_dry_run() {
+    let pds = FakePds::start().await;
+    mount_mirror_fixture(&pds).await;
+    pds.expect_follow("did:plc:new1", 0).await;
+
+    let output = pds.run(&["mirror-follows", "curated.test", "--dry-run"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let stdout = stdout(&output);
+    assert!(stdout.contains("2 accounts to follow, 4 skipped"), "{}", stdout);
+    assert!(stdout.contains("Would follow @new1.test"));
+    assert!(stdout.contains("Would follow @new2.test"));
+}
+
+#[tokio::test]
+async fn test_mirror_follows_respects_max() {
+    let pds = FakePds::start().await;
+    mount_mirror_fixture(&pds).await;
+    pds.expect_follow("did:plc:new1", 1).await;
+    pds.expect_follow("did:plc:new2", 0).await;
+
+    let output = pds
+        .run(&["mirror-follows", "curated.test", "--limit", "1", "--per-minute", "6000", "--yes"])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Followed 1 accounts (0 failed)"));
+}
+
+#[tokio::test]
+async fn test_mirror_follows_asks_for_confirmation() {
+    let pds = FakePds::start().await;
+    mount_mirror_fixture(&pds).await;
+    pds.expect_follow("did:plc:new1", 0).await;
+
+    let output = pds.run(&["mirror-follows", "curated.test"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Aborted"));
+}
+
+#[tokio::test]
+async fn test_mirror_follows_with_appview_keeps_viewer_state() {
+    let pds = FakePds::start().await;
+    mount_mirror_fixture(&pds).await;
+    // Public AppView reads carry no viewer state, so nothing may be read from it directly
+    let appview = wiremock::MockServer::start().await;
+    pds.expect_follow("did:plc:new1", 0).await;
+
+    let output = pds
+        .run(&["mirror-follows", "curated.test", "--dry-run", "--appview", &appview.uri()])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("2 accounts to follow, 4 skipped"), "{}", stdout(&output));
+    assert!(appview.received_requests().await.unwrap().is_empty());
+
+    let proxy = format!("did:web:{}#bsky_appview", appview.address().to_string().replace(':', "%3A"));
+    let reads: Vec<_> = pds
+        .server
+        .received_requests()
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|request| request.url.path().starts_with("/xrpc/app.bsky."))
+        .collect();
+    assert!(!reads.is_empty());
+    for request in reads {
+        assert_eq!(request.headers.get("atproto-proxy").unwrap(), proxy.as_str());
+        assert_eq!(request.headers.get("authorization").unwrap(), "Bearer access-1");
+    }
+}
+
+#[tokio::test]
+async fn test_profile_and_lookup() {
+    let pds = FakePds::start().await;
+    pds.login();
+    pds.mount_profile(MY_DID, MY_HANDLE, 12, 34).await;
+    pds.mount_profile("did:plc:other", "other.test", 5, 6).await;
+
+    let output = pds.run(&["profile"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let stdout_text = stdout(&output);
+    assert!(stdout_text.contains("Profile for @me.test"));
+    assert!(stdout_text.contains("Followers: 12"));
+    assert!(stdout_text.contains("Following: 34"));
+
+    let output = pds.run(&["lookup", "@Other.test"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("DID: did:plc:other"));
+
+    let output = pds.run(&["lookup", "not a handle"]).await;
+    assert!(!output.status.success());
+}
+
+#[tokio::test]
+async fn test_shell_runs_commands_until_exit() {
+    let pds = FakePds::start().await;
+    pds.login();
+    pds.mount_profile(MY_DID, MY_HANDLE, 12, 34).await;
+    pds.mount_profile("did:plc:other", "other.test", 5, 6).await;
+
+    let input = "profile\nbogus\n-o json lookup '@other.test'\nshell\nexit\nprofile\n";
+    let output = pds.run_with_stdin(&["shell"], input).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let stdout = stdout(&output);
+    assert_eq!(stdout.matches("Followers: 12").count(), 1, "{}", stdout);
+    assert!(stdout.contains("\"did\": \"did:plc:other\""));
+    let stderr = stderr(&output);
+    assert!(stderr.contains("unrecognized subcommand 'bogus'"));
+    assert!(stderr.contains("Already in the shell"));
+
+    let history = std::fs::read_to_string(pds.data_dir().join("shell_history")).unwrap();
+    assert!(history.contains("-o json lookup '@other.test'"));
+}
+
+#[tokio::test]
+async fn test_shell_applies_db_path_and_account_switch() {
+    let pds = FakePds::start().await;
+    pds.login();
+    pds.login_as("did:plc:brand", "brand.test");
+    pds.mount_graph(GET_FOLLOWERS, MY_DID, vec![vec![account("did:plc:a", "a.test")]])
+        .await;
+    pds.mount_graph(GET_FOLLOWERS, "did:plc:brand", vec![vec![account("did:plc:b", "b.test")]])
+        .await;
+
+    let other_db = pds.data_dir().join("other.db");
+    let input = format!(
+        "save-followers\nconfig set db_path '{}'\naccounts switch me.test\nsave-followers\n",
+        other_db.display()
+    );
+    let output = pds
+        .run_with_stdin(&["--account", "brand.test", "shell"], &input)
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let owners = |path: &std::path::Path| -> Vec<String> {
+        Connection::open(path)
+            .unwrap()
+            .prepare("SELECT owner_did FROM followers")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    };
+    assert_eq!(owners(&pds.db_path()), ["did:plc:brand"]);
+    assert_eq!(owners(&other_db), [MY_DID]);
+}
+
+#[tokio::test]
+async fn test_follow_and_unfollow_by_handle() {
+    let pds = FakePds::start().await;
+    pds.login();
+    pds.mount_handle("friend.test", "did:plc:friend").await;
+    pds.expect_follow("did:plc:friend", 1).await;
+    pds.expect_unfollow("did:plc:friend", "3kfriend", 1).await;
+
+    let output = pds.run(&["follow", "friend.test"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Followed friend.test (at://did:plc:me/app.bsky.graph.follow/"));
+
+    let output = pds.run(&["unfollow", "friend.test", "--yes"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Unfollowed friend.test"));
+}
+
+#[tokio::test]
+async fn test_compare_followers_records_diffs() {
+    let pds = FakePds::start().await;
+    pds.login();
+    pds.mount_graph(GET_FOLLOWERS, MY_DID, vec![vec![account("did:plc:a", "a.test")]])
+        .await;
+    let output = pds.run(&["save-followers"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    pds.server.reset().await;
+    pds.mount_graph(GET_FOLLOWERS, MY_DID, vec![vec![account("did:plc:b", "b.test")]])
+        .await;
+    let output = pds.run(&["compare-followers"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let stdout = stdout(&output);
+    assert!(stdout.contains("New follower: @b.test"));
+    assert!(stdout.contains("Unfollower: @a.test"));
+
+    // The compared list becomes the baseline, so nothing is recorded twice
+    let output = pds.run(&["compare-followers"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(common::stdout(&output).contains("No follower changes"));
+
+    let conn = Connection::open(pds.db_path()).unwrap();
+    let actions: Vec<(String, String)> = conn
+        .prepare("SELECT handle, action FROM follower_diffs ORDER BY id")
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        actions,
+        [
+            ("b.test".to_string(), "follow".to_string()),
+            ("a.test".to_string(), "unfollow".to_string())
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_first_compare_followers_only_saves_a_baseline() {
+    let pds = FakePds::start().await;
+    pds.login();
+    pds.mount_graph(GET_FOLLOWERS, MY_DID, vec![vec![account("did:plc:a", "a.test")]])
+        .await;
+
+    let output = pds.run(&["compare-followers"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("No follower changes"));
+
+    let conn = Connection::open(pds.db_path()).unwrap();
+    let diffs: i64 = conn
+        .query_row("SELECT COUNT(*) FROM follower_diffs", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(diffs, 0);
+    let followers: i64 = conn
+        .query_row("SELECT COUNT(*) FROM followers", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(followers, 1);
+}
+
+#[tokio::test]
+async fn test_machine_readable_output() {
+    let pds = FakePds::start().await;
+    pds.login();
+    pds.mount_profile(MY_DID, MY_HANDLE, 12, 34).await;
+    pds.mount_graph(
+        GET_FOLLOWERS,
+        MY_DID,
+        vec![vec![account("did:plc:a", "a.test"), account("did:plc:b", "b.test")]],
+    )
+    .await;
+
+    let output = pds.run(&["profile", "--output", "json"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let profile: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(profile["handle"], MY_HANDLE);
+    assert_eq!(profile["followersCount"], 12);
+
+    pds.run(&["save-followers"]).await;
+    pds.server.reset().await;
+    pds.mount_graph(GET_FOLLOWERS, MY_DID, vec![vec![account("did:plc:b", "b.test")]])
+        .await;
+    let output = pds.run(&["compare-followers", "-o", "csv"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "action,did,handle\nunfollow,did:plc:a,a.test\n");
+}
+
+#[tokio::test]
+async fn test_config_profiles() {
+    let pds = FakePds::start().await;
+    pds.login();
+    pds.mount_profile(MY_DID, MY_HANDLE, 12, 34).await;
+    pds.mount_graph(GET_FOLLOWERS, MY_DID, vec![vec![account("did:plc:a", "a.test")]])
+        .await;
+
+    let db_path = pds.data_dir().join("work.db");
+    let db_path = db_path.to_str().unwrap();
+    for (key, value) in [("db_path", db_path), ("output", "json")] {
+        let output = pds.run(&["--profile", "work", "config", "set", key, value]).await;
+        assert!(output.status.success(), "{}", stderr(&output));
+    }
+    let output = pds.run(&["config", "set", "per_minute", "often"]).await;
+    assert!(!output.status.success());
+    assert!(std::fs::read_to_string(pds.config_path()).unwrap().contains("[profiles.work]"));
+
+    let output = pds.run(&["--profile", "work", "config", "show"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let settings: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(settings["db_path"], db_path);
+
+    // The profile's database and output format apply; flags still win
+    let output = pds.run(&["--profile", "work", "save-followers"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(std::path::Path::new(db_path).exists());
+    assert!(!pds.db_path().exists());
+    let saved: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(saved["count"], 1);
+
+    let output = pds.run(&["--profile", "work", "-o", "text", "profile"]).await;
+    assert!(stdout(&output).contains("Profile for @me.test"));
+
+    let output = pds.run(&["--profile", "missing", "profile"]).await;
+    assert!(!output.status.success());
+}
+
+#[tokio::test]
+async fn test_logout_removes_session() {
+    let pds = FakePds::start().await;
+    pds.login();
+
+    let output = pds.run(&["logout"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let sessions = std::fs::read_to_string(pds.data_dir().join("sessions.json")).unwrap();
+    assert!(!sessions.contains(MY_DID));
+
+    let output = pds.run(&["profile"]).await;
+    assert!(!output.status.success());
+}
+
+#[tokio::test]
+async fn test_accounts_list_switch_and_remove() {
+    let pds = FakePds::start().await;
+    pds.login();
+    pds.login_as("did:plc:brand", "brand.test");
+
+    let output = pds.run(&["accounts", "list"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let stdout_text = stdout(&output);
+    assert!(stdout_text.contains("* @me.test (did:plc:me)"));
+    assert!(stdout_text.contains("  @brand.test (did:plc:brand)"));
+
+    let output = pds.run(&["accounts", "switch", "@brand.test"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(pds.read_session()["did"], "did:plc:brand");
+
+    let output = pds.run(&["accounts", "switch", "nobody.test"]).await;
+    assert!(!output.status.success());
+
+    let output = pds.run(&["accounts", "remove", "brand.test", "--yes"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(pds.read_session()["did"], MY_DID);
+
+    let output = pds.run(&["accounts", "list", "-o", "csv"]).await;
+    assert_eq!(
+        stdout(&output),
+        format!("did,handle,service,active\n{},{},{},true\n", MY_DID, MY_HANDLE, pds.server.uri())
+    );
+}
+
+#[tokio::test]
+async fn test_account_flag_scopes_saved_followers() {
+    let pds = FakePds::start().await;
+    pds.login();
+    pds.login_as("did:plc:brand", "brand.test");
+    pds.mount_graph(GET_FOLLOWERS, MY_DID, vec![vec![account("did:plc:a", "a.test")]])
+        .await;
+    pds.mount_graph(GET_FOLLOWERS, "did:plc:brand", vec![vec![account("did:plc:b", "b.test")]])
+        .await;
+
+    for args in [
+        vec!["save-followers"],
+        vec!["--account", "brand.test", "save-followers"],
+        vec!["compare-followers"],
+    ] {
+        let output = pds.run(&args).await;
+        assert!(output.status.success(), "{}", stderr(&output));
+    }
+
+    let conn = Connection::open(pds.db_path()).unwrap();
+    let rows: Vec<(String, String)> = conn
+        .prepare("SELECT owner_did, did FROM followers ORDER BY owner_did")
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        rows,
+        [
+            ("did:plc:brand".to_string(), "did:plc:b".to_string()),
+            (MY_DID.to_string(), "did:plc:a".to_string())
+        ]
+    );
+    let diffs: i64 = conn
+        .query_row("SELECT COUNT(*) FROM follower_diffs", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(diffs, 0);
+}
+
+#[tokio::test]
+async fn test_db_migrate_upgrades_original_layout() {
+    let pds = FakePds::start().await;
+    std::fs::create_dir_all(pds.data_dir()).unwrap();
+    Connection::open(pds.db_path())
+        .unwrap()
+        .execute_batch(
+            "CREATE TABLE followers (did TEXT PRIMARY KEY, handle TEXT NOT NULL, indexed_at DATETIME NOT NULL);
+            CREATE TABLE follower_diffs (id INTEGER PRIMARY KEY AUTOINCREMENT, did TEXT NOT NULL,
+                handle TEXT NOT NULL, action TEXT NOT NULL, timestamp DATETIME NOT NULL);
+            CREATE TABLE config (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+            CREATE TABLE handles (id INTEGER PRIMARY KEY AUTOINCREMENT, handle TEXT NOT NULL);
+            INSERT INTO followers VALUES ('did:plc:a', 'a.test', '2024-01-01T00:00:00Z');
+            INSERT INTO handles (handle) VALUES ('me.test');",
+        )
+        .unwrap();
+
+    let output = pds.run(&["db", "status"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let status = stdout(&output);
+    assert!(status.contains("Schema version: 0 of 5"), "{}", status);
+    assert!(status.contains("  2: Scope followers and follower diffs to the owning account"));
+
+    let output = pds.run(&["db", "migrate"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("from schema version 0 to 5"));
+    assert_eq!(stored_followers(&pds), ["a.test"]);
+
+    let output = pds.run(&["-o", "json", "db", "status"]).await;
+    let status: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(status["version"], 5);
+    assert_eq!(status["pending"], serde_json::json!([]));
+
+    let output = pds.run(&["db", "migrate"]).await;
+    assert!(stdout(&output).contains("already at schema version 5"));
+
+    // The original rows become the first snapshot of the account the handles table names
+    pds.login();
+    let output = pds.run(&["snapshots", "show", "2024-01-01"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("@a.test (did:plc:a)"));
+}
+
+#[tokio::test]
+async fn test_newer_schema_is_a_database_error() {
+    let pds = FakePds::start().await;
+    pds.login();
+    std::fs::create_dir_all(pds.data_dir()).unwrap();
+    Connection::open(pds.db_path()).unwrap().pragma_update(None, "user_version", 999).unwrap();
+
+    for args in [vec!["db", "status"], vec!["db", "migrate"], vec!["snapshots", "list"]] {
+        let output = pds.run(&args).await;
+        assert_eq!(output.status.code(), Some(7), "{:?}: {}", args, stderr(&output));
+        assert!(stderr(&output).contains("newer than this build supports"), "{}", stderr(&output));
+    }
+
+    let output = pds.run(&["-o", "json", "snapshots", "list"]).await;
+    let envelope: serde_json::Value = serde_json::from_str(&stderr(&output)).unwrap();
+    assert_eq!(envelope["error"]["category"], "database");
+    assert_eq!(envelope["error"]["exit_code"], 7);
+}
+
+#[tokio::test]
+async fn test_other_account_does_not_adopt_original_rows() {
+    let pds = FakePds::start().await;
+    std::fs::create_dir_all(pds.data_dir()).unwrap();
+    Connection::open(pds.db_path())
+        .unwrap()
+        .execute_batch(
+            "CREATE TABLE followers (did TEXT PRIMARY KEY, handle TEXT NOT NULL, indexed_at DATETIME NOT NULL);
+            CREATE TABLE follower_diffs (id INTEGER PRIMARY KEY AUTOINCREMENT, did TEXT NOT NULL,
+                handle TEXT NOT NULL, action TEXT NOT NULL, timestamp DATETIME NOT NULL);
+            CREATE TABLE handles (id INTEGER PRIMARY KEY AUTOINCREMENT, handle TEXT NOT NULL);
+            INSERT INTO followers VALUES ('did:plc:a', 'a.test', '2024-01-01T00:00:00Z');
+            INSERT INTO handles (handle) VALUES ('me.test');",
+        )
+        .unwrap();
+    let output = pds.run(&["db", "migrate"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    // A second account logs in and uses the database before the original owner
+    pds.login_as("did:plc:brand", "brand.test");
+    pds.mount_graph(GET_FOLLOWERS, "did:plc:brand", vec![vec![account("did:plc:b", "b.test")]])
+        .await;
+    let output = pds.run(&["compare-followers"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!stdout(&output).contains("a.test"), "{}", stdout(&output));
+
+    let output = pds.run(&["db", "status"]).await;
+    assert!(
+        stdout(&output).contains("1 snapshots and 0 recorded changes from before multi-account support have no owner"),
+        "{}",
+        stdout(&output)
+    );
+
+    pds.login();
+    let output = pds.run(&["--account", MY_HANDLE, "snapshots", "show", "2024-01-01"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("@a.test (did:plc:a)"));
+    let output = pds.run(&["-o", "json", "db", "status"]).await;
+    let status: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(status["unowned_snapshots"], 0);
+}
+
+#[tokio::test]
+async fn test_handle_changes_are_recorded_and_shown() {
+    let pds = FakePds::start().await;
+    pds.login();
+    pds.mount_graph(GET_FOLLOWERS, MY_DID, vec![vec![account("did:plc:other", "old.test")]])
+        .await;
+    pds.run(&["save-followers"]).await;
+
+    pds.server.reset().await;
+    pds.mount_graph(GET_FOLLOWERS, MY_DID, vec![vec![account("did:plc:other", "other.test")]])
+        .await;
+    pds.mount_profile("did:plc:other", "other.test", 5, 6).await;
+    let output = pds.run(&["compare-followers"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let text = stdout(&output);
+    assert!(text.contains("Handle change: @old.test is now @other.test"), "{}", text);
+    assert!(!text.contains("New follower"));
+
+    let conn = Connection::open(pds.db_path()).unwrap();
+    let recorded: (String, String, String) = conn
+        .query_row("SELECT action, handle, old_handle FROM follower_diffs", [], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })
+        .unwrap();
+    assert_eq!(
+        recorded,
+        ("handle_change".to_string(), "other.test".to_string(), "old.test".to_string())
+    );
+
+    let output = pds.run(&["lookup", "other.test"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let text = stdout(&output);
+    assert!(text.contains("Handle history:"), "{}", text);
+    assert!(text.contains("  @old.test  "));
+
+    let output = pds.run(&["-o", "json", "lookup", "other.test"]).await;
+    let lookup: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(lookup["did"], "did:plc:other");
+    assert_eq!(lookup["handle_history"][0]["handle"], "old.test");
+    assert_eq!(lookup["handle_history"][1]["handle"], "other.test");
+
+    // Another stored account never saw the old handle
+    pds.login_as("did:plc:brand", "brand.test");
+    let output = pds.run(&["--account", "brand.test", "-o", "json", "lookup", "other.test"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let lookup: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(lookup["handle_history"], serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn test_diff_between_snapshots() {
+    let pds = FakePds::start().await;
+    pds.login();
+    pds.mount_graph(
+        GET_FOLLOWERS,
+        MY_DID,
+        vec![vec![account("did:plc:a", "a.test"), account("did:plc:b", "b.test")]],
+    )
+    .await;
+    pds.run(&["save-followers"]).await;
+
+    pds.server.reset().await;
+    pds.mount_graph(
+        GET_FOLLOWERS,
+        MY_DID,
+        vec![vec![account("did:plc:b", "b2.test"), account("did:plc:c", "c.test")]],
+    )
+    .await;
+    pds.run(&["save-followers"]).await;
+
+    let output = pds.run(&["diff", "--from", "2000-01-01"]).await;
+    assert_eq!(output.status.code(), Some(3), "{}", stderr(&output));
+
+    let first = pds.run(&["diff", "--from", "1"]).await;
+    assert!(first.status.success(), "{}", stderr(&first));
+    let text = stdout(&first);
+    assert!(text.contains("+ @c.test (did:plc:c)"), "{}", text);
+    assert!(text.contains("- @a.test (did:plc:a)"));
+    assert!(text.contains("~ @b.test is now @b2.test (did:plc:b)"));
+    assert_eq!(stdout(&pds.run(&["diff", "--from", "1"]).await), text);
+
+    let output = pds.run(&["-o", "jsonl", "diff", "--from", "1", "--to", "latest"]).await;
+    let records: Vec<serde_json::Value> = stdout(&output)
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let actions: Vec<&str> = records.iter().map(|r| r["action"].as_str().unwrap()).collect();
+    assert_eq!(actions, ["added", "removed", "handle_change"]);
+    assert_eq!(records[2]["old_handle"], "b.test");
+
+    let output = pds.run(&["diff", "--from", "latest"]).await;
+    assert!(stdout(&output).contains("No changes"));
+
+    // Offline comparisons record nothing
+    let conn = Connection::open(pds.db_path()).unwrap();
+    let recorded: i64 = conn
+        .query_row("SELECT COUNT(*) FROM follower_diffs", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(recorded, 0);
+}
+
+#[tokio::test]
+async fn test_relationship_commands() {
+    let pds = FakePds::start().await;
+    pds.login();
+    pds.mount_graph(
+        GET_FOLLOWERS,
+        MY_DID,
+        vec![vec![account("did:plc:a", "a.test"), account("did:plc:fan", "fan.test")]],
+    )
+    .await;
+    pds.mount_graph(
+        GET_FOLLOWS,
+        MY_DID,
+        vec![vec![account("did:plc:a", "a.test"), account("did:plc:idol", "idol.test")]],
+    )
+    .await;
+
+    let output = pds.run(&["save-followers"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = pds.run(&["mutuals"]).await;
+    assert_eq!(output.status.code(), Some(3));
+    assert!(stderr(&output).contains("run save-following"));
+
+    let output = pds.run(&["save-following"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Saved 2 follows to database"));
+
+    let output = pds.run(&["mutuals"]).await;
+    assert_eq!(stdout(&output).trim(), "@a.test (did:plc:a)");
+    let output = pds.run(&["fans"]).await;
+    assert_eq!(stdout(&output).trim(), "@fan.test (did:plc:fan)");
+    let output = pds.run(&["-o", "csv", "non-reciprocal"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "did,handle\ndid:plc:idol,idol.test\n");
+}
+
+#[tokio::test]
+async fn test_snapshots_keep_history() {
+    let pds = FakePds::start().await;
+    pds.login();
+    pds.mount_graph(GET_FOLLOWERS, MY_DID, vec![vec![account("did:plc:a", "a.test")]])
+        .await;
+    pds.run(&["save-followers"]).await;
+    pds.run(&["save-followers"]).await;
+
+    pds.server.reset().await;
+    pds.mount_graph(GET_FOLLOWERS, MY_DID, vec![vec![account("did:plc:b", "b.test")]])
+        .await;
+    pds.run(&["save-followers"]).await;
+    assert_eq!(stored_followers(&pds), ["b.test"]);
+
+    let output = pds.run(&["-o", "jsonl", "snapshots", "list"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let snapshots: Vec<serde_json::Value> = stdout(&output)
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(snapshots.len(), 2, "an unchanged list is not stored twice");
+    assert_eq!(snapshots[0]["kind"], "followers");
+
+    let first = snapshots[0]["id"].to_string();
+    let output = pds.run(&["snapshots", "show", &first]).await;
+    assert!(stdout(&output).contains("@a.test (did:plc:a)"));
+    let output = pds.run(&["snapshots", "show", "2100-01-01"]).await;
+    assert!(stdout(&output).contains("@b.test (did:plc:b)"));
+
+    let output = pds.run(&["snapshots", "show", "2000-01-01"]).await;
+    assert_eq!(output.status.code(), Some(3));
+    let output = pds.run(&["snapshots", "show", "--kind", "following", "yesterday"]).await;
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[tokio::test]
+async fn test_single_account_session_is_imported() {
+    let pds = FakePds::start().await;
+    pds.mount_profile(MY_DID, MY_HANDLE, 1, 2).await;
+    std::fs::create_dir_all(pds.data_dir()).unwrap();
+    let session = serde_json::json!({
+        "access_jwt": "access-1",
+        "refresh_jwt": "refresh-1",
+        "handle": MY_HANDLE,
+        "did": MY_DID,
+        "email": ""
+    });
+    std::fs::write(pds.data_dir().join("session.json"), session.to_string()).unwrap();
+
+    let output = pds.run(&["profile"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!pds.data_dir().join("session.json").exists());
+    assert_eq!(pds.read_session()["did"], MY_DID);
+    // Remembered as the owner of data from before multi-account support
+    let store: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(pds.data_dir().join("sessions.json")).unwrap()).unwrap();
+    assert_eq!(store["imported"], MY_DID);
+}
+
+/// Polls the watch heartbeat until `ready` accepts it
+async fn wait_for_heartbeat(pds: &FakePds, ready: impl Fn(&serde_json::Value) -> bool) -> serde_json::Value {
+    let path = pds.data_dir().join("watch-heartbeat.json");
+    for _ in 0..200 {
+        if let Ok(json) = std::fs::read_to_string(&path) {
+            if let Ok(heartbeat) = serde_json::from_str::<serde_json::Value>(&json) {
+                if ready(&heartbeat) {
+                    return heartbeat;
+                }
+            }
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    panic!("heartbeat never became ready");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_watch_records_changes_and_stops_on_sigterm() {
+    let pds = FakePds::start().await;
+    pds.login();
+    pds.mount_graph(GET_FOLLOWERS, MY_DID, vec![vec![account("did:plc:a", "a.test")]])
+        .await;
+    pds.mount_graph(GET_FOLLOWS, MY_DID, vec![vec![account("did:plc:x", "x.test")]])
+        .await;
+
+    let child = pds
+        .command()
+        .args(["watch", "--interval", "1s", "-o", "jsonl"])
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    wait_for_heartbeat(&pds, |h| h["snapshots"] == 1).await;
+
+    // A transient outage is retried instead of ending the watch
+    pds.server.reset().await;
+    wait_for_heartbeat(&pds, |h| h["consecutive_failures"] == 1).await;
+
+    pds.mount_graph(GET_FOLLOWERS, MY_DID, vec![vec![account("did:plc:b", "b.test")]])
+        .await;
+    pds.mount_graph(
+        GET_FOLLOWS,
+        MY_DID,
+        vec![vec![account("did:plc:x", "x.test"), account("did:plc:y", "y.test")]],
+    )
+    .await;
+    wait_for_heartbeat(&pds, |h| h["snapshots"] == 2).await;
+
+    let pid = child.id().unwrap().to_string();
+    let status = std::process::Command::new("kill").args(["-TERM", &pid]).status().unwrap();
+    assert!(status.success());
+    let output = child.wait_with_output().await.unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let heartbeat = wait_for_heartbeat(&pds, |h| h["stopped"] == true).await;
+    assert_eq!(heartbeat["consecutive_failures"], 0);
+    assert!(stdout(&output).contains(r#"{"action":"start_following","did":"did:plc:y","handle":"y.test"}"#));
+
+    let conn = Connection::open(pds.db_path()).unwrap();
+    let actions: Vec<(String, String)> = conn
+        .prepare("SELECT handle, action FROM follower_diffs ORDER BY id")
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        actions,
+        [
+            ("b.test".to_string(), "follow".to_string()),
+            ("a.test".to_string(), "unfollow".to_string()),
+            ("y.test".to_string(), "start_following".to_string())
+        ]
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_watch_after_save_followers_baselines_follows_only() {
+    let pds = FakePds::start().await;
+    pds.login();
+    pds.mount_graph(GET_FOLLOWERS, MY_DID, vec![vec![account("did:plc:a", "a.test")]])
+        .await;
+    let output = pds.run(&["save-followers"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    pds.server.reset().await;
+    pds.mount_graph(
+        GET_FOLLOWERS,
+        MY_DID,
+        vec![vec![account("did:plc:a", "a.test"), account("did:plc:b", "b.test")]],
+    )
+    .await;
+    pds.mount_graph(GET_FOLLOWS, MY_DID, vec![vec![account("did:plc:x", "x.test")]])
+        .await;
+
+    let child = pds
+        .command()
+        .args(["watch", "--interval", "1h"])
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    wait_for_heartbeat(&pds, |h| h["snapshots"] == 1).await;
+
+    let pid = child.id().unwrap().to_string();
+    let status = std::process::Command::new("kill").args(["-TERM", &pid]).status().unwrap();
+    assert!(status.success());
+    let output = child.wait_with_output().await.unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    // The follows list had no snapshot to compare against; followers did
+    let stdout = stdout(&output);
+    assert!(stdout.contains("Took first follows snapshot"), "{}", stdout);
+    assert!(stdout.contains("New follower: @b.test"), "{}", stdout);
+    assert!(!stdout.contains("x.test"), "{}", stdout);
+
+    let conn = Connection::open(pds.db_path()).unwrap();
+    let actions: Vec<(String, String)> = conn
+        .prepare("SELECT handle, action FROM follower_diffs ORDER BY id")
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(actions, [("b.test".to_string(), "follow".to_string())]);
+}
+
+#[tokio::test]
+async fn test_watch_rejects_zero_interval() {
+    let pds = FakePds::start().await;
+    pds.login();
+
+    let output = pds.run(&["watch", "--interval", "0"]).await;
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("greater than zero"), "{}", stderr(&output));
+
+    let output = pds.command().env("BSKY_WATCH_INTERVAL", "0s").arg("watch").output().await.unwrap();
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[tokio::test]
+async fn test_tui_rejects_zero_refresh() {
+    let pds = FakePds::start().await;
+    pds.login();
+
+    // Rejected while parsing, before the terminal is switched to raw mode
+    let output = pds.run(&["tui", "--refresh", "0"]).await;
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("greater than zero"), "{}", stderr(&output));
+}
+
+#[tokio::test]
+async fn test_login_without_terminal() {
+    let pds = FakePds::start().await;
+    pds.mount_session().await;
+
+    // Neither a handle nor a password can be prompted for
+    let output = pds.run(&["login"]).await;
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("--handle"), "{}", stderr(&output));
+
+    let output = pds.run(&["--handle", MY_HANDLE, "login"]).await;
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("BSKY_APP_PASSWORD"), "{}", stderr(&output));
+
+    let output = pds
+        .command()
+        .args(["--handle", MY_HANDLE, "login"])
+        .env("BSKY_APP_PASSWORD", "app-pass")
+        .output()
+        .await
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(pds.read_session()["did"], MY_DID);
+}
+
+#[tokio::test]
+async fn test_login_with_password_stdin_and_file() {
+    use tokio::io::AsyncWriteExt;
+
+    let pds = FakePds::start().await;
+    pds.mount_session().await;
+
+    let mut child = pds
+        .command()
+        .args(["--handle", MY_HANDLE, "login", "--password-stdin"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(b"app-pass\n").await.unwrap();
+    drop(stdin);
+    let output = child.wait_with_output().await.unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Logged in as @me.test"));
+
+    let password_file = pds.data_dir().join("password");
+    std::fs::write(&password_file, "app-pass\n").unwrap();
+    let output = pds
+        .run(&["--handle", MY_HANDLE, "login", "--password-file", password_file.to_str().unwrap()])
+        .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[tokio::test]
+async fn test_login_with_auth_factor_token() {
+    let pds = FakePds::start().await;
+    pds.require_auth_factor("app-pass", "123456").await;
+
+    let login = |token: Option<&'static str>| {
+        let mut command = pds.command();
+        command.args(["--handle", MY_HANDLE, "login"]).env("BSKY_APP_PASSWORD", "app-pass");
+        if let Some(token) = token {
+            command.args(["--auth-factor-token", token]);
+        }
+        command
+    };
+
+    let output = login(None).output().await.unwrap();
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("--auth-factor-token"), "{}", stderr(&output));
+
+    let output = login(Some("000000")).output().await.unwrap();
+    assert!(!output.status.success());
+
+    let output = login(Some("123456")).output().await.unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(pds.read_session()["handle"], MY_HANDLE);
+}
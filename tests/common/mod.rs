@@ -0,0 +1,319 @@
+//! In-process fake PDS/AppView for driving the CLI without network access
+
+#![allow(dead_code)]
+
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::process::Output;
+use tempfile::TempDir;
+use tokio::process::Command;
+use wiremock::matchers::{body_partial_json, header, method, path, query_param, query_param_is_missing};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+pub const MY_DID: &str = "did:plc:me";
+pub const MY_HANDLE: &str = "me.test";
+
+/// A scripted XRPC server plus a throwaway data directory for the binary
+pub struct FakePds {
+    pub server: MockServer,
+    home: TempDir,
+}
+
+/// A profile view as returned in graph listings
+pub fn account(did: &str, handle: &str) -> Value {
+    json!({
+        "did": did,
+        "handle": handle,
+        "indexedAt": "2024-01-01T00:00:00.000Z"
+    })
+}
+
+impl FakePds {
+    pub async fn start() -> Self {
+        Self {
+            server: MockServer::start().await,
+            home: tempfile::tempdir().unwrap(),
+        }
+    }
+
+    /// Directory the binary keeps `sessions.json` and `followers.db` in
+    pub fn data_dir(&self) -> PathBuf {
+        self.home.path().join("data").join("bsky")
+    }
+
+    pub fn config_path(&self) -> PathBuf {
+        self.home.path().join("config").join("bsky").join("config.toml")
+    }
+
+    pub fn db_path(&self) -> PathBuf {
+        self.data_dir().join("followers.db")
+    }
+
+    /// Writes a session as if `login` had already succeeded against this server
+    pub fn login(&self) {
+        self.login_as(MY_DID, MY_HANDLE);
+    }
+
+    /// Adds a session for another account; the first account stored is active
+    pub fn login_as(&self, did: &str, handle: &str) {
+        std::fs::create_dir_all(self.data_dir()).unwrap();
+        let path = self.data_dir().join("sessions.json");
+        let mut store: Value = match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).unwrap(),
+            Err(_) => json!({ "active": did, "sessions": {} }),
+        };
+        store["sessions"][did] = json!({
+            "access_jwt": "access-1",
+            "refresh_jwt": "refresh-1",
+            "handle": handle,
+            "did": did,
+            "email": "",
+            "service_endpoint": self.server.uri()
+        });
+        std::fs::write(path, serde_json::to_string_pretty(&store).unwrap()).unwrap();
+    }
+
+    /// The stored session of the active account
+    pub fn read_session(&self) -> Value {
+        let json = std::fs::read_to_string(self.data_dir().join("sessions.json")).unwrap();
+        let store: Value = serde_json::from_str(&json).unwrap();
+        let active = store["active"].as_str().unwrap();
+        store["sessions"][active].clone()
+    }
+
+    /// Answers createSession and refreshSession for our account
+    pub async fn mount_session(&self) {
+        Mock::given(method("POST"))
+            .and(path("/xrpc/com.atproto.server.createSession"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(self.session_body()))
+            .mount(&self.server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path("/xrpc/com.atproto.server.refreshSession"))
+            .and(header("authorization", "Bearer refresh-1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "accessJwt": "access-2",
+                "refreshJwt": "refresh-2",
+                "handle": MY_HANDLE,
+                "did": MY_DID
+            })))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// createSession response for our account, hosted on this server
+    fn session_body(&self) -> Value {
+        json!({
+            "accessJwt": "access-1",
+            "refreshJwt": "refresh-1",
+            "handle": MY_HANDLE,
+            "did": MY_DID,
+            "didDoc": {
+                "id": MY_DID,
+                "service": [{
+                    "id": "#atproto_pds",
+                    "type": "AtprotoPersonalDataServer",
+                    "serviceEndpoint": self.server.uri()
+                }]
+            }
+        })
+    }
+
+    /// Makes createSession demand email 2FA and accept only `token`
+    pub async fn require_auth_factor(&self, password: &str, token: &str) {
+        Mock::given(method("POST"))
+            .and(path("/xrpc/com.atproto.server.createSession"))
+            .and(body_partial_json(json!({ "password": password, "authFactorToken": token })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(self.session_body()))
+            .with_priority(1)
+            .mount(&self.server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path("/xrpc/com.atproto.server.createSession"))
+            .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+                "error": "AuthFactorTokenRequired",
+                "message": "A sign in code has been sent to your email address"
+            })))
+            .with_priority(2)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Makes every request carrying the first access token fail with ExpiredToken
+    pub async fn expire_first_token(&self) {
+        Mock::given(header("authorization", "Bearer access-1"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+                "error": "ExpiredToken",
+                "message": "Token has expired"
+            })))
+            .with_priority(1)
+            .mount(&self.server)
+            .await;
+    }
+
+    pub async fn mount_profile(&self, did: &str, handle: &str, followers: u32, follows: u32) {
+        for actor in [did, handle] {
+            Mock::given(method("GET"))
+                .and(path("/xrpc/app.bsky.actor.getProfile"))
+                .and(query_param("actor", actor))
+                .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                    "did": did,
+                    "handle": handle,
+                    "displayName": "Test Account",
+                    "followersCount": followers,
+                    "followsCount": follows,
+                    "viewer": {}
+                })))
+                .mount(&self.server)
+                .await;
+        }
+    }
+
+    /// Answers resolveHandle for `handle`
+    pub async fn mount_handle(&self, handle: &str, did: &str) {
+        Mock::given(method("GET"))
+            .and(path("/xrpc/com.atproto.identity.resolveHandle"))
+            .and(query_param("handle", handle))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "did": did })))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Makes us follow `did` through the record with key `rkey`, and expects it deleted
+    pub async fn expect_unfollow(&self, did: &str, rkey: &str, times: u64) {
+        let uri = format!("at://{}/app.bsky.graph.follow/{}", MY_DID, rkey);
+        Mock::given(method("GET"))
+            .and(path("/xrpc/app.bsky.actor.getProfile"))
+            .and(query_param("actor", did))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "did": did,
+                "handle": "followed.test",
+                "viewer": { "following": uri }
+            })))
+            .mount(&self.server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path("/xrpc/com.atproto.repo.getRecord"))
+            .and(query_param("rkey", rkey))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "uri": uri,
+                "cid": "bafyreifollow",
+                "value": { "subject": did }
+            })))
+            .mount(&self.server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path("/xrpc/com.atproto.repo.deleteRecord"))
+            .and(body_partial_json(json!({
+                "repo": MY_DID,
+                "collection": "app.bsky.graph.follow",
+                "rkey": rkey
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .expect(times)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Serves a getFollowers/getFollows listing split into cursor-linked pages
+    pub async fn mount_graph(&self, nsid: &str, actor: &str, pages: Vec<Vec<Value>>) {
+        let key = if nsid.ends_with("getFollowers") { "followers" } else { "follows" };
+        let page_count = pages.len();
+
+        for (i, page) in pages.into_iter().enumerate() {
+            let mut body = json!({ "subject": { "did": actor, "handle": actor }, key: page });
+            if i + 1 < page_count {
+                body["cursor"] = json!(format!("page-{}", i + 1));
+            }
+
+            let mock = Mock::given(method("GET"))
+                .and(path(format!("/xrpc/{}", nsid)))
+                .and(query_param("actor", actor));
+            let mock = if i == 0 {
+                mock.and(query_param_is_missing("cursor"))
+            } else {
+                mock.and(query_param("cursor", format!("page-{}", i)))
+            };
+            mock.respond_with(ResponseTemplate::new(200).set_body_json(body))
+                .mount(&self.server)
+                .await;
+        }
+    }
+
+    /// Answers getProfiles with the given (active) accounts regardless of the query
+    pub async fn mount_profiles(&self, profiles: Vec<Value>) {
+        Mock::given(method("GET"))
+            .and(path("/xrpc/app.bsky.actor.getProfiles"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "profiles": profiles })))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Expects exactly `times` follow records to be created for `subject`
+    pub async fn expect_follow(&self, subject: &str, times: u64) {
+        Mock::given(method("POST"))
+            .and(path("/xrpc/com.atproto.repo.createRecord"))
+            .and(body_partial_json(json!({
+                "repo": MY_DID,
+                "collection": "app.bsky.graph.follow",
+                "record": { "subject": subject }
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "uri": format!("at://{}/app.bsky.graph.follow/3k{}", MY_DID, subject.len()),
+                "cid": "bafyreitest"
+            })))
+            .expect(times)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Builds a command for the CLI binary pointed at this server and data dir
+    pub fn command(&self) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_bsky-rusty-tools"));
+        command
+            .env_clear()
+            .env("HOME", self.home.path())
+            .env("XDG_DATA_HOME", self.home.path().join("data"))
+            .env("XDG_CONFIG_HOME", self.home.path().join("config"))
+            .env("BSKY_SERVICE", self.server.uri())
+            .env("BSKY_PLC_DIRECTORY", self.server.uri())
+            .current_dir(self.home.path())
+            .kill_on_drop(true);
+        command
+    }
+
+    /// Runs the CLI with `args` and returns its output
+    pub async fn run(&self, args: &[&str]) -> Output {
+        self.command().args(args).output().await.unwrap()
+    }
+
+    /// Runs the CLI with `args`, feeding it `input` on stdin
+    pub async fn run_with_stdin(&self, args: &[&str], input: &str) -> Output {
+        use tokio::io::AsyncWriteExt;
+
+        let mut child = self
+            .command()
+            .args(args)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut stdin = child.stdin.take().unwrap();
+        stdin.write_all(input.as_bytes()).await.unwrap();
+        drop(stdin);
+        child.wait_with_output().await.unwrap()
+    }
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use bsky_rusty_tools::api::{get_followers, PageOptions};
+use bsky_rusty_tools::auth::Session;
+use bsky_rusty_tools::client::XrpcClient;
+use bsky_rusty_tools::db::{diff_followers, Account};
+use bsky_rusty_tools::output::{render, OutputFormat};
+use common::{account, FakePds, MY_DID, MY_HANDLE};
+
+fn session(pds: &FakePds) -> Session {
+    Session {
+        access_jwt: "access-1".to_string(),
+        refresh_jwt: "refresh-1".to_string(),
+        handle: MY_HANDLE.to_string(),
+        did: MY_DID.to_string(),
+        email: String::new(),
+        service_endpoint: Some(pds.server.uri()),
+    }
+}
+
+#[tokio::test]
+async fn test_followers_and_diff_through_the_library() {
+    let pds = FakePds::start().await;
+    pds.mount_graph(
+        "app.bsky.graph.getFollowers",
+        MY_DID,
+        vec![vec![account("did:plc:a", "a.test")], vec![account("did:plc:b", "b.test")]],
+    )
+    .await;
+
+    let client = XrpcClient::new(&pds.server.uri()).unwrap().with_session(session(&pds));
+    let current: Vec<Account> = get_followers(&client, PageOptions::default())
+        .await
+        .unwrap()
+        .iter()
+        .map(Account::from)
+        .collect();
+    assert_eq!(current.len(), 2);
+
+    let previous = vec![Account { did: "did:plc:a".to_string(), handle: "a.test".to_string() }];
+    let diff = diff_followers(&previous, &current);
+    assert_eq!(diff.new_followers[0].handle, "b.test");
+    assert_eq!(
+        render(&diff, OutputFormat::Csv).unwrap(),
+        "action,did,handle\nfollow,did:plc:b,b.test"
+    );
+}